@@ -1,7 +1,12 @@
-use crate::API_BASE_URL;
+use crate::client::{ApiClient, ApiRequest};
+use crate::error::FlyError;
+use crate::middleware::Middleware;
+use crate::retry::RetryPolicy;
+use crate::token::{StaticToken, TokenProvider};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::error::Error;
+use std::sync::Arc;
+use tracing::debug;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AppResponse {
@@ -35,95 +40,77 @@ pub struct CreateAppRequest {
     pub org_slug: String,
 }
 
+#[derive(Clone)]
 pub struct AppManager {
-    client: Client,
-    api_token: String,
+    api: ApiClient,
 }
 
 impl AppManager {
     pub fn new(client: Client, api_token: String) -> Self {
-        Self { client, api_token }
+        Self::from_api(ApiClient::new(
+            client,
+            Arc::new(StaticToken::new(&api_token)),
+        ))
     }
 
-    pub async fn create(
-        &self,
-        app_name: &str,
-        org_slug: &str,
-    ) -> Result<AppResponse, Box<dyn Error>> {
-        let url = format!("{}/apps", API_BASE_URL);
-        let request_body = CreateAppRequest {
-            app_name: app_name.to_string(),
-            org_slug: org_slug.to_string(),
-        };
+    pub(crate) fn from_api(api: ApiClient) -> Self {
+        Self { api }
+    }
 
-        let response = self
-            .client
-            .post(&url)
-            .bearer_auth(&self.api_token)
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
-            .await?;
+    /// Looks up the API token from `token_provider` for every request.
+    pub fn with_token_provider(mut self, token_provider: impl TokenProvider + 'static) -> Self {
+        self.api.set_token_provider(Arc::new(token_provider));
+        self
+    }
 
-        if response.status() == reqwest::StatusCode::CREATED {
-            let response_text = response.text().await?;
-            let app_response: AppResponse = serde_json::from_str(&response_text)?;
-            println!("Created app: {:?}", app_response);
-            Ok(app_response)
-        } else {
-            let error_message = response.text().await?;
-            println!("Error response: {}", error_message);
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "App creation failed",
-            )))
-        }
+    /// Sends requests to `base_url` instead of [`API_BASE_URL`](crate::API_BASE_URL).
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.api.set_base_url(base_url);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.api.set_retry_policy(retry_policy);
+        self
     }
 
-    pub async fn delete(&self, app_name: &str, force: bool) -> Result<(), Box<dyn Error>> {
-        let url = if force {
-            format!("{}/apps/{}?force=true", API_BASE_URL, app_name)
-        } else {
-            format!("{}/apps/{}", API_BASE_URL, app_name)
+    pub fn with_middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.api.push_middleware(Arc::new(middleware));
+        self
+    }
+
+    pub async fn create(&self, app_name: &str, org_slug: &str) -> Result<AppResponse, FlyError> {
+        let request_body = CreateAppRequest {
+            app_name: app_name.to_string(),
+            org_slug: org_slug.to_string(),
         };
 
-        let response = self
-            .client
-            .delete(&url)
-            .bearer_auth(&self.api_token)
-            .send()
+        let app_response: AppResponse = self
+            .api
+            .json(ApiRequest::post("/apps").json(&request_body))
             .await?;
+        debug!("Created app: {:?}", app_response);
+        Ok(app_response)
+    }
 
-        if response.status() == reqwest::StatusCode::ACCEPTED {
-            println!("Deleted app {}", app_name);
-        } else {
-            println!("Failed to delete {}: {:?}", app_name, response.status());
+    pub async fn delete(&self, app_name: &str, force: bool) -> Result<(), FlyError> {
+        let mut request = ApiRequest::delete("/apps/{app}").param("app", app_name);
+        if force {
+            request = request.query("force", true);
         }
 
+        self.api.execute(request).await?;
+        debug!("Deleted app {}", app_name);
+
         Ok(())
     }
 
-    pub async fn list(&self, org_slug: &str) -> Result<Vec<App>, Box<dyn Error>> {
-        let url = format!("{}/apps?org_slug={}", API_BASE_URL, org_slug);
-
-        let response = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.api_token)
-            .send()
+    pub async fn list(&self, org_slug: &str) -> Result<Vec<App>, FlyError> {
+        let apps_response: AppsResponse = self
+            .api
+            .json(ApiRequest::get("/apps").query("org_slug", org_slug))
             .await?;
-
-        if response.status() == reqwest::StatusCode::OK {
-            let response_text = response.text().await?;
-            let apps_response: AppsResponse = serde_json::from_str(&response_text)?;
-            println!("List of apps: {:?}", apps_response.apps);
-            Ok(apps_response.apps)
-        } else {
-            println!("Failed to list apps: {:?}", response.status());
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Failed to list apps",
-            )))
-        }
+        debug!("List of apps: {:?}", apps_response.apps);
+        Ok(apps_response.apps)
     }
 }
@@ -0,0 +1,417 @@
+//! Synchronous client, enabled with the `blocking` feature.
+//!
+//! The managers mirror the async ones method for method and share their request and
+//! response types. Each call drives the async implementation on a small runtime owned by
+//! the client, so they must not be called from inside an async runtime.
+//!
+//! A few machine methods are intentionally async-only: `watch_events` and
+//! `watch_events_with` return a stream, and `acquire_lease` and `with_lease` rely on a
+//! lease refreshed in the background. Use `create_lease`, `refresh_lease` and
+//! `with_lease_nonce` to manage leases by hand, and `list_events` to poll for events.
+//!
+//! ```rust,no_run
+//! use fly_sdk::blocking::FlyControl;
+//!
+//! # fn main() -> Result<(), fly_sdk::FlyError> {
+//! let fly = FlyControl::new("your_api_token".to_string())?;
+//! let apps = fly.apps.list("personal")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::FlyError;
+use crate::FlyControlBuilder;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+/// Generates synchronous wrappers that block on the async method of the same name.
+macro_rules! blocking_methods {
+    ($(fn $name:ident(&self $(, $arg:ident: $ty:ty)* $(,)?) -> $ret:ty;)*) => {
+        $(
+            pub fn $name(&self $(, $arg: $ty)*) -> $ret {
+                self.runtime.block_on(self.inner.$name($($arg),*))
+            }
+        )*
+    };
+}
+
+/// Blocking counterpart of [`crate::FlyControl`].
+#[derive(Clone)]
+pub struct FlyControl {
+    #[cfg(feature = "apps")]
+    pub apps: AppManager,
+
+    #[cfg(feature = "machines")]
+    pub machines: MachineManager,
+
+    #[cfg(feature = "volumes")]
+    pub volumes: VolumeManager,
+
+    #[cfg(feature = "secrets")]
+    pub secrets: SecretsManager,
+}
+
+impl FlyControl {
+    pub fn new(api_token: String) -> Result<Self, FlyError> {
+        Self::from_async(crate::FlyControl::new(api_token))
+    }
+
+    /// See [`crate::FlyControl::from_env`].
+    pub fn from_env() -> Result<Self, FlyError> {
+        Self::from_async(crate::FlyControl::from_env()?)
+    }
+
+    /// Returns the async builder; finish it with [`FlyControlBuilder::build_blocking`].
+    pub fn builder() -> FlyControlBuilder {
+        FlyControlBuilder::new()
+    }
+
+    /// Wraps an async client, keeping its configuration and shared rate limiter.
+    #[allow(unused_variables)]
+    pub fn from_async(inner: crate::FlyControl) -> Result<Self, FlyError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map(Arc::new)
+            .map_err(|err| FlyError::Config(format!("failed to start runtime: {}", err)))?;
+
+        Ok(FlyControl {
+            #[cfg(feature = "apps")]
+            apps: AppManager {
+                inner: inner.apps,
+                runtime: runtime.clone(),
+            },
+
+            #[cfg(feature = "machines")]
+            machines: MachineManager {
+                inner: inner.machines,
+                runtime: runtime.clone(),
+            },
+
+            #[cfg(feature = "volumes")]
+            volumes: VolumeManager {
+                inner: inner.volumes,
+                runtime: runtime.clone(),
+            },
+
+            #[cfg(feature = "secrets")]
+            secrets: SecretsManager {
+                inner: inner.secrets,
+                runtime: runtime.clone(),
+            },
+        })
+    }
+}
+
+impl FlyControlBuilder {
+    pub fn build_blocking(self) -> Result<FlyControl, FlyError> {
+        FlyControl::from_async(self.build()?)
+    }
+}
+
+#[cfg(feature = "apps")]
+pub use apps_manager::AppManager;
+
+#[cfg(feature = "apps")]
+mod apps_manager {
+    use super::*;
+    use crate::apps::{self, App, AppResponse};
+
+    /// Blocking counterpart of [`crate::apps::AppManager`].
+    #[derive(Clone)]
+    pub struct AppManager {
+        pub(super) inner: apps::AppManager,
+        pub(super) runtime: Arc<Runtime>,
+    }
+
+    impl AppManager {
+        blocking_methods! {
+            fn create(&self, app_name: &str, org_slug: &str) -> Result<AppResponse, FlyError>;
+            fn delete(&self, app_name: &str, force: bool) -> Result<(), FlyError>;
+            fn list(&self, org_slug: &str) -> Result<Vec<App>, FlyError>;
+        }
+    }
+}
+
+#[cfg(feature = "machines")]
+pub use machines_manager::MachineManager;
+
+#[cfg(feature = "machines")]
+mod machines_manager {
+    use super::*;
+    use crate::machines::{
+        self, CloneOptions, CommandResponse, CreateManyOptions, CreateManyReport, DeployReport,
+        DrainOptions, EventResponse, ExecOutput, ExecRequest, Lease, ListMachinesQuery,
+        MachineConfig, MachineRequest, MachineResponse, MachineSize, MachineState, ProcessResponse,
+        Replicas, RollingDeploy, Signal, StartResponse, StopOptions,
+    };
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    /// Blocking counterpart of [`crate::machines::MachineManager`].
+    #[derive(Clone)]
+    pub struct MachineManager {
+        pub(super) inner: machines::MachineManager,
+        pub(super) runtime: Arc<Runtime>,
+    }
+
+    impl MachineManager {
+        /// See [`crate::machines::MachineManager::with_lease_nonce`]; the blocking client has
+        /// no background refresh, so leases are managed by hand.
+        pub fn with_lease_nonce(&self, nonce: &str) -> Self {
+            Self {
+                inner: self.inner.with_lease_nonce(nonce),
+                runtime: self.runtime.clone(),
+            }
+        }
+
+        blocking_methods! {
+            fn create(
+                &self,
+                app_name: &str,
+                request_data: MachineRequest,
+            ) -> Result<MachineResponse, FlyError>;
+            fn create_many(
+                &self,
+                app_name: &str,
+                template: &MachineRequest,
+                replicas: impl Into<Replicas>,
+                options: CreateManyOptions,
+            ) -> Result<CreateManyReport, FlyError>;
+            fn resize(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+                size: impl Into<MachineSize>,
+            ) -> Result<MachineResponse, FlyError>;
+            fn clone_machine(
+                &self,
+                app_name: &str,
+                source_id: &str,
+                options: CloneOptions,
+            ) -> Result<MachineResponse, FlyError>;
+            fn deploy(
+                &self,
+                app_name: &str,
+                update: impl Fn(&mut MachineConfig) + Send + Sync,
+                strategy: RollingDeploy,
+            ) -> Result<DeployReport, FlyError>;
+            fn list(&self, app_name: &str) -> Result<Vec<MachineResponse>, FlyError>;
+            fn list_with(
+                &self,
+                app_name: &str,
+                query: ListMachinesQuery,
+            ) -> Result<Vec<MachineResponse>, FlyError>;
+            fn stop(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+                instance_id: &str,
+            ) -> Result<(), FlyError>;
+            fn stop_with(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+                options: StopOptions,
+            ) -> Result<(), FlyError>;
+            fn signal(&self, app_name: &str, machine_id: &str, signal: Signal) -> Result<(), FlyError>;
+            fn start(&self, app_name: &str, machine_id: &str) -> Result<StartResponse, FlyError>;
+            fn suspend(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+                instance_id: &str,
+            ) -> Result<(), FlyError>;
+            fn delete(&self, app_name: &str, machine_id: &str, force: bool) -> Result<(), FlyError>;
+            fn wait_for_machine_state(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+                desired_state: MachineState,
+                timeout: Option<u64>,
+                instance_id: Option<&str>,
+            ) -> Result<MachineResponse, FlyError>;
+            fn wait_for_states(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+                states: &[MachineState],
+                timeout: Duration,
+                instance_id: Option<&str>,
+            ) -> Result<MachineResponse, FlyError>;
+            fn update_machine(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+                instance_id: &str,
+                machine_request: MachineRequest,
+            ) -> Result<MachineResponse, FlyError>;
+            fn restart_machine(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+                instance_id: &str,
+            ) -> Result<MachineResponse, FlyError>;
+            fn list_events(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+            ) -> Result<Vec<EventResponse>, FlyError>;
+            fn list_processes(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+            ) -> Result<Vec<ProcessResponse>, FlyError>;
+            fn execute_command(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+                command: Vec<&str>,
+                timeout: Option<u64>,
+            ) -> Result<CommandResponse, FlyError>;
+            fn exec(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+                request: ExecRequest,
+            ) -> Result<ExecOutput, FlyError>;
+            fn get_machine(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+            ) -> Result<MachineResponse, FlyError>;
+            fn cordon(&self, app_name: &str, machine_id: &str) -> Result<(), FlyError>;
+            fn uncordon(&self, app_name: &str, machine_id: &str) -> Result<(), FlyError>;
+            fn drain(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+                options: DrainOptions,
+            ) -> Result<bool, FlyError>;
+            fn get_metadata(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+            ) -> Result<HashMap<String, String>, FlyError>;
+            fn set_metadata_key(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+                key: &str,
+                value: &str,
+            ) -> Result<(), FlyError>;
+            fn delete_metadata_key(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+                key: &str,
+            ) -> Result<(), FlyError>;
+            fn get_lease(&self, app_name: &str, machine_id: &str) -> Result<Lease, FlyError>;
+            fn create_lease(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+                ttl: u64,
+                description: Option<&str>,
+            ) -> Result<Lease, FlyError>;
+            fn refresh_lease(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+                nonce: &str,
+                ttl: u64,
+            ) -> Result<Lease, FlyError>;
+            fn release_lease(
+                &self,
+                app_name: &str,
+                machine_id: &str,
+                nonce: &str,
+            ) -> Result<(), FlyError>;
+        }
+    }
+}
+
+#[cfg(feature = "volumes")]
+pub use volumes_manager::VolumeManager;
+
+#[cfg(feature = "volumes")]
+mod volumes_manager {
+    use super::*;
+    use crate::volumes::{
+        self, CreateVolumeRequest, ExtendVolumeRequest, Snapshot, UpdateVolumeRequest, Volume,
+    };
+
+    /// Blocking counterpart of [`crate::volumes::VolumeManager`].
+    #[derive(Clone)]
+    pub struct VolumeManager {
+        pub(super) inner: volumes::VolumeManager,
+        pub(super) runtime: Arc<Runtime>,
+    }
+
+    impl VolumeManager {
+        blocking_methods! {
+            fn list_volumes(&self, app_name: &str, summary: bool) -> Result<Vec<Volume>, FlyError>;
+            fn create_volume(
+                &self,
+                app_name: &str,
+                volume_request: CreateVolumeRequest,
+            ) -> Result<Volume, FlyError>;
+            fn get_volume(&self, app_name: &str, volume_id: &str) -> Result<Volume, FlyError>;
+            fn update_volume(
+                &self,
+                app_name: &str,
+                volume_id: &str,
+                update_request: UpdateVolumeRequest,
+            ) -> Result<Volume, FlyError>;
+            fn destroy_volume(&self, app_name: &str, volume_id: &str) -> Result<(), FlyError>;
+            fn extend_volume(
+                &self,
+                app_name: &str,
+                volume_id: &str,
+                extend_request: ExtendVolumeRequest,
+            ) -> Result<Volume, FlyError>;
+            fn list_snapshots(
+                &self,
+                app_name: &str,
+                volume_id: &str,
+            ) -> Result<Vec<Snapshot>, FlyError>;
+            fn create_snapshot(&self, app_name: &str, volume_id: &str) -> Result<(), FlyError>;
+        }
+    }
+}
+
+#[cfg(feature = "secrets")]
+pub use secrets_manager::SecretsManager;
+
+#[cfg(feature = "secrets")]
+mod secrets_manager {
+    use super::*;
+    use crate::secrets::{self, Secret, SecretValue};
+
+    /// Blocking counterpart of [`crate::secrets::SecretsManager`].
+    #[derive(Clone)]
+    pub struct SecretsManager {
+        pub(super) inner: secrets::SecretsManager,
+        pub(super) runtime: Arc<Runtime>,
+    }
+
+    impl SecretsManager {
+        blocking_methods! {
+            fn list_secrets(&self, app_name: &str) -> Result<Vec<Secret>, FlyError>;
+            fn create_secret(
+                &self,
+                app_name: &str,
+                secret_label: &str,
+                secret_type: &str,
+                value_request: SecretValue,
+            ) -> Result<Secret, FlyError>;
+            fn generate_secret(
+                &self,
+                app_name: &str,
+                secret_label: &str,
+                secret_type: &str,
+            ) -> Result<(), FlyError>;
+            fn destroy_secret(&self, app_name: &str, secret_label: &str) -> Result<(), FlyError>;
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use crate::error::{check_response, decode_response, request_id, FlyError};
+use crate::middleware::Middleware;
+use crate::rate_limit::{RateLimitAction, RateLimiter};
+use crate::retry::RetryPolicy;
+use crate::token::TokenProvider;
+use crate::API_BASE_URL;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Client, Method, Request, Response};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::field::Empty;
+use tracing::{debug, info_span, Instrument, Span};
+
+/// Characters escaped in path parameters so each stays a single path segment.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// A single Fly API call, described independently of the HTTP client.
+///
+/// `route` is a template such as `/apps/{app}/machines/{machine}`; each `{name}` is
+/// replaced by the matching [`ApiRequest::param`], percent-encoded.
+pub(crate) struct ApiRequest {
+    method: Method,
+    route: &'static str,
+    params: Vec<(&'static str, String)>,
+    query: Vec<(String, String)>,
+    headers: Vec<(&'static str, String)>,
+    body: Option<Result<Vec<u8>, serde_json::Error>>,
+    action: Option<RateLimitAction>,
+    retry: bool,
+    idempotent: bool,
+    timeout: Option<Duration>,
+}
+
+impl ApiRequest {
+    pub(crate) fn new(method: Method, route: &'static str) -> Self {
+        Self {
+            method,
+            route,
+            params: Vec::new(),
+            query: Vec::new(),
+            headers: Vec::new(),
+            body: None,
+            action: None,
+            retry: true,
+            idempotent: false,
+            timeout: None,
+        }
+    }
+
+    pub(crate) fn get(route: &'static str) -> Self {
+        Self::new(Method::GET, route)
+    }
+
+    pub(crate) fn post(route: &'static str) -> Self {
+        Self::new(Method::POST, route)
+    }
+
+    #[cfg(feature = "volumes")]
+    pub(crate) fn put(route: &'static str) -> Self {
+        Self::new(Method::PUT, route)
+    }
+
+    pub(crate) fn delete(route: &'static str) -> Self {
+        Self::new(Method::DELETE, route)
+    }
+
+    pub(crate) fn param(mut self, name: &'static str, value: &str) -> Self {
+        self.params.push((name, value.to_string()));
+        self
+    }
+
+    #[cfg(any(feature = "apps", feature = "machines", feature = "volumes"))]
+    pub(crate) fn query(mut self, name: &str, value: impl ToString) -> Self {
+        self.query.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    #[cfg(feature = "machines")]
+    pub(crate) fn header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers.push((name, value.to_string()));
+        self
+    }
+
+    pub(crate) fn json<B: Serialize + ?Sized>(mut self, body: &B) -> Self {
+        self.body = Some(serde_json::to_vec(body));
+        self
+    }
+
+    /// Throttles every attempt of the request, retries included, with the rate limiter
+    /// bucket for `action`.
+    #[cfg(feature = "machines")]
+    pub(crate) fn action(mut self, action: RateLimitAction) -> Self {
+        self.action = Some(action);
+        self
+    }
+
+    /// Sends the request once, for calls whose caller already loops on failures.
+    #[cfg(feature = "machines")]
+    pub(crate) fn without_retry(mut self) -> Self {
+        self.retry = false;
+        self
+    }
+
+    /// Marks a `POST` that is safe to send again, such as starting a machine, so it is
+    /// retried when [`RetryPolicy::retry_mutations`] is enabled.
+    #[cfg(feature = "machines")]
+    pub(crate) fn idempotent(mut self) -> Self {
+        self.idempotent = true;
+        self
+    }
+
+    /// Overrides the client-wide timeout, for calls the API may hold open for longer.
+    #[cfg(feature = "machines")]
+    pub(crate) fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    fn path(&self) -> String {
+        self.params
+            .iter()
+            .fold(self.route.to_string(), |path, (name, value)| {
+                let value = utf8_percent_encode(value, PATH_SEGMENT).to_string();
+                path.replace(&format!("{{{}}}", name), &value)
+            })
+    }
+}
+
+/// Shared executor used by every manager: applies authentication, rate limiting,
+/// middleware and retries, and turns failed responses into [`FlyError`]s.
+#[derive(Clone)]
+pub(crate) struct ApiClient {
+    http: Client,
+    token: Arc<dyn TokenProvider>,
+    base_url: String,
+    retry_policy: RetryPolicy,
+    rate_limiter: RateLimiter,
+    middleware: Arc<Vec<Arc<dyn Middleware>>>,
+}
+
+impl ApiClient {
+    pub(crate) fn new(http: Client, token: Arc<dyn TokenProvider>) -> Self {
+        Self {
+            http,
+            token,
+            base_url: API_BASE_URL.to_string(),
+            retry_policy: RetryPolicy::default(),
+            rate_limiter: RateLimiter::default(),
+            middleware: Arc::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn set_token_provider(&mut self, token: Arc<dyn TokenProvider>) {
+        self.token = token;
+    }
+
+    pub(crate) fn set_base_url(&mut self, base_url: &str) {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+    }
+
+    pub(crate) fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    pub(crate) fn set_rate_limiter(&mut self, rate_limiter: RateLimiter) {
+        self.rate_limiter = rate_limiter;
+    }
+
+    pub(crate) fn push_middleware(&mut self, middleware: Arc<dyn Middleware>) {
+        Arc::make_mut(&mut self.middleware).push(middleware);
+    }
+
+    /// Sends the request and decodes a successful JSON response into `T`.
+    pub(crate) async fn json<T: DeserializeOwned>(
+        &self,
+        request: ApiRequest,
+    ) -> Result<T, FlyError> {
+        decode_response(self.send(request).await?).await
+    }
+
+    /// Sends the request and discards the body of a successful response.
+    pub(crate) async fn execute(&self, request: ApiRequest) -> Result<(), FlyError> {
+        self.send(request).await.map(|_| ())
+    }
+
+    /// Sends the request, returning the response only if it was successful.
+    ///
+    /// The call runs inside a `fly_api` span carrying the app, machine and volume ids,
+    /// method, route template, status, latency and Fly request id.
+    pub(crate) async fn send(&self, request: ApiRequest) -> Result<Response, FlyError> {
+        let span = info_span!(
+            "fly_api",
+            method = %request.method,
+            route = request.route,
+            app = Empty,
+            machine_id = Empty,
+            volume_id = Empty,
+            status = Empty,
+            latency_ms = Empty,
+            request_id = Empty,
+        );
+        for (name, value) in &request.params {
+            let field = match *name {
+                "app" => "app",
+                "machine" => "machine_id",
+                "volume" => "volume_id",
+                _ => continue,
+            };
+            span.record(field, value.as_str());
+        }
+
+        self.send_in_span(request).instrument(span).await
+    }
+
+    async fn send_in_span(&self, request: ApiRequest) -> Result<Response, FlyError> {
+        let action = request.action;
+        let retry = request.retry
+            && self
+                .retry_policy
+                .allows(&request.method, request.idempotent);
+        let http_request = self.build(request)?;
+
+        let started_at = Instant::now();
+        let result = if retry {
+            self.send_with_retry(http_request, action).await
+        } else {
+            self.attempt(http_request, action).await
+        };
+        let span = Span::current();
+        span.record("latency_ms", started_at.elapsed().as_millis() as u64);
+
+        let response = match result {
+            Ok(response) => response,
+            Err(err) => {
+                debug!(error = %err, "Fly API request failed");
+                return Err(err);
+            }
+        };
+        span.record("status", response.status().as_u16());
+        if let Some(request_id) = request_id(&response) {
+            span.record("request_id", request_id.as_str());
+        }
+
+        let result = check_response(response).await;
+        match &result {
+            Ok(_) => debug!("Fly API request succeeded"),
+            Err(err) => debug!(error = %err, "Fly API request failed"),
+        }
+        result
+    }
+
+    fn build(&self, request: ApiRequest) -> Result<Request, FlyError> {
+        let url = format!("{}{}", self.base_url, request.path());
+        let token = self.token.token()?;
+        let mut builder = self.http.request(request.method, url);
+        // macaroon tokens from `fly tokens create` already carry their `FlyV1` scheme
+        builder = if token.starts_with("FlyV1 ") {
+            builder.header(AUTHORIZATION, token)
+        } else {
+            builder.bearer_auth(token)
+        };
+
+        if !request.query.is_empty() {
+            builder = builder.query(&request.query);
+        }
+        for (name, value) in &request.headers {
+            builder = builder.header(*name, value);
+        }
+        if let Some(timeout) = request.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(body) = request.body {
+            builder = builder
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.map_err(FlyError::Encode)?);
+        }
+
+        Ok(builder.build()?)
+    }
+
+    /// Sends the request once, after taking a rate limiter token for `action`.
+    async fn attempt(
+        &self,
+        mut request: Request,
+        action: Option<RateLimitAction>,
+    ) -> Result<Response, FlyError> {
+        if let Some(action) = action {
+            self.rate_limiter.acquire(action).await;
+        }
+        for middleware in self.middleware.iter() {
+            middleware.on_request(&mut request)?;
+        }
+
+        // keep a copy of the request for the response hooks; bodies here are always buffered
+        let sent = request.try_clone();
+        let result = self.http.execute(request).await;
+        if let Some(sent) = &sent {
+            for middleware in self.middleware.iter() {
+                match &result {
+                    Ok(response) => middleware.on_response(sent, response),
+                    Err(err) => middleware.on_error(sent, err),
+                }
+            }
+        }
+
+        Ok(result?)
+    }
+
+    async fn send_with_retry(
+        &self,
+        request: Request,
+        action: Option<RateLimitAction>,
+    ) -> Result<Response, FlyError> {
+        let policy = &self.retry_policy;
+        let mut attempt = 1;
+        loop {
+            let Some(next) = request.try_clone() else {
+                return self.attempt(request, action).await;
+            };
+            let last_attempt = attempt >= policy.attempt_limit();
+
+            let delay = match self.attempt(next, action).await {
+                Ok(response) if !last_attempt && policy.retries_status(response.status()) => {
+                    debug!(
+                        "Retrying {} {} after status {} (attempt {}/{})",
+                        request.method(),
+                        request.url().path(),
+                        response.status(),
+                        attempt,
+                        policy.attempt_limit()
+                    );
+                    policy.delay(attempt, crate::retry::retry_after(&response))
+                }
+                Err(FlyError::Transport(err))
+                    if !last_attempt && (err.is_connect() || err.is_timeout()) =>
+                {
+                    debug!(
+                        "Retrying {} {} after error: {} (attempt {}/{})",
+                        request.method(),
+                        request.url().path(),
+                        err,
+                        attempt,
+                        policy.attempt_limit()
+                    );
+                    policy.delay(attempt, None)
+                }
+                result => return result,
+            };
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use reqwest::{Response, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::fmt;
+use std::time::Duration;
+
+/// Header Fly attaches to every Machines API response, useful when contacting Fly support.
+pub const REQUEST_ID_HEADER: &str = "fly-request-id";
+
+/// Details of a non-successful response returned by the Fly API.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+    pub request_id: Option<String>,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.status)?;
+        if !self.message.is_empty() {
+            write!(f, ": {}", self.message)?;
+        }
+        if let Some(request_id) = &self.request_id {
+            write!(f, " (request id: {})", request_id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Error returned by every manager in the SDK.
+#[derive(Debug)]
+pub enum FlyError {
+    /// The request could not be sent or the response body could not be read.
+    Transport(reqwest::Error),
+    /// The request body could not be serialized.
+    Encode(serde_json::Error),
+    /// The response body did not match the expected type.
+    Decode {
+        source: serde_json::Error,
+        body: String,
+        request_id: Option<String>,
+    },
+    /// 401 or 403: the token is missing, invalid or lacks access to the resource.
+    Unauthorized(ApiError),
+    /// 404: the app, machine, volume or secret does not exist.
+    NotFound(ApiError),
+    /// 409 or 412: the resource was changed concurrently or is in a conflicting state.
+    Conflict(ApiError),
+    /// Any other non-successful response.
+    Api(ApiError),
+    /// The SDK was configured with invalid or missing settings.
+    Config(String),
+    /// A machine did not reach the expected state before the deadline.
+    Timeout {
+        machine_id: String,
+        expected: String,
+        last_state: Option<String>,
+        waited: Duration,
+    },
+    /// A command run on a machine exited with a non-zero code, was killed by a signal, or
+    /// finished without reporting an exit code.
+    CommandFailed {
+        exit_code: Option<i32>,
+        exit_signal: Option<i32>,
+        stderr: String,
+    },
+    /// A machine's health checks did not all pass before the deadline.
+    HealthCheckFailed {
+        machine_id: String,
+        check: Option<String>,
+        output: Option<String>,
+    },
+}
+
+impl FlyError {
+    /// Builds an error from a non-successful response, reading Fly's error message and request id.
+    pub(crate) async fn from_response(response: Response) -> Self {
+        let status = response.status();
+        let request_id = request_id(&response);
+        let body = match response.text().await {
+            Ok(body) => body,
+            Err(err) => return FlyError::Transport(err),
+        };
+
+        Self::from_parts(status, request_id, &body)
+    }
+
+    pub(crate) fn from_parts(status: StatusCode, request_id: Option<String>, body: &str) -> Self {
+        #[derive(Deserialize)]
+        struct ErrorBody {
+            error: String,
+        }
+
+        let message = serde_json::from_str::<ErrorBody>(body)
+            .map(|body| body.error)
+            .unwrap_or_else(|_| body.trim().to_string());
+        let api_error = ApiError {
+            status,
+            message,
+            request_id,
+        };
+
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => FlyError::Unauthorized(api_error),
+            StatusCode::NOT_FOUND => FlyError::NotFound(api_error),
+            StatusCode::CONFLICT | StatusCode::PRECONDITION_FAILED => FlyError::Conflict(api_error),
+            _ => FlyError::Api(api_error),
+        }
+    }
+
+    pub(crate) fn decode(
+        source: serde_json::Error,
+        body: &str,
+        request_id: Option<String>,
+    ) -> Self {
+        FlyError::Decode {
+            source,
+            body: body.to_string(),
+            request_id,
+        }
+    }
+
+    /// The API error details, if the error came from a non-successful response.
+    pub fn api_error(&self) -> Option<&ApiError> {
+        match self {
+            FlyError::Unauthorized(err)
+            | FlyError::NotFound(err)
+            | FlyError::Conflict(err)
+            | FlyError::Api(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// The HTTP status of the response, if there was one.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            FlyError::Transport(err) => err.status(),
+            _ => self.api_error().map(|err| err.status),
+        }
+    }
+
+    /// The `fly-request-id` of the failed or undecodable response, if Fly sent one.
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            FlyError::Decode { request_id, .. } => request_id.as_deref(),
+            _ => self.api_error().and_then(|err| err.request_id.as_deref()),
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, FlyError::NotFound(_))
+    }
+
+    pub fn is_conflict(&self) -> bool {
+        matches!(self, FlyError::Conflict(_))
+    }
+
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(self, FlyError::Unauthorized(_))
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, FlyError::Timeout { .. })
+    }
+
+    pub fn is_command_failed(&self) -> bool {
+        matches!(self, FlyError::CommandFailed { .. })
+    }
+
+    pub fn is_health_check_failed(&self) -> bool {
+        matches!(self, FlyError::HealthCheckFailed { .. })
+    }
+}
+
+impl fmt::Display for FlyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlyError::Transport(err) => write!(f, "request to Fly API failed: {}", err),
+            FlyError::Encode(err) => write!(f, "failed to encode request body: {}", err),
+            FlyError::Decode { source, .. } => {
+                write!(f, "failed to decode Fly API response: {}", source)
+            }
+            FlyError::Unauthorized(err) => write!(f, "unauthorized: {}", err),
+            FlyError::NotFound(err) => write!(f, "not found: {}", err),
+            FlyError::Conflict(err) => write!(f, "conflict: {}", err),
+            FlyError::Api(err) => write!(f, "Fly API error: {}", err),
+            FlyError::Config(message) => write!(f, "invalid configuration: {}", message),
+            FlyError::Timeout {
+                machine_id,
+                expected,
+                last_state,
+                waited,
+            } => {
+                write!(
+                    f,
+                    "machine {} did not become {} within {:?}",
+                    machine_id, expected, waited
+                )?;
+                match last_state {
+                    Some(state) => write!(f, " (last state: {})", state),
+                    None => Ok(()),
+                }
+            }
+            FlyError::CommandFailed {
+                exit_code,
+                exit_signal,
+                stderr,
+            } => {
+                match (exit_signal, exit_code) {
+                    (Some(signal), _) => write!(f, "command killed by signal {}", signal)?,
+                    (None, Some(code)) => write!(f, "command exited with code {}", code)?,
+                    (None, None) => write!(f, "command finished without an exit code")?,
+                }
+                match stderr.trim() {
+                    "" => Ok(()),
+                    stderr => write!(f, ": {}", stderr),
+                }
+            }
+            FlyError::HealthCheckFailed {
+                machine_id,
+                check,
+                output,
+            } => {
+                write!(f, "health checks of machine {} did not pass", machine_id)?;
+                if let Some(check) = check {
+                    write!(f, " (check {}", check)?;
+                    if let Some(output) = output.as_deref().map(str::trim).filter(|o| !o.is_empty())
+                    {
+                        write!(f, ": {}", output)?;
+                    }
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for FlyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FlyError::Transport(err) => Some(err),
+            FlyError::Encode(err) => Some(err),
+            FlyError::Decode { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for FlyError {
+    fn from(err: reqwest::Error) -> Self {
+        FlyError::Transport(err)
+    }
+}
+
+/// The `fly-request-id` header of a response.
+pub(crate) fn request_id(response: &Response) -> Option<String> {
+    response
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Returns the response if it was successful, otherwise converts it into a [`FlyError`].
+pub(crate) async fn check_response(response: Response) -> Result<Response, FlyError> {
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(FlyError::from_response(response).await)
+    }
+}
+
+/// Checks the response status and decodes a successful JSON body into `T`.
+pub(crate) async fn decode_response<T: DeserializeOwned>(
+    response: Response,
+) -> Result<T, FlyError> {
+    let response = check_response(response).await?;
+    let request_id = request_id(&response);
+    let body = response.text().await?;
+    serde_json::from_str(&body).map_err(|err| FlyError::decode(err, &body, request_id))
+}
@@ -15,6 +15,21 @@
 //! // Now you can use fly_control to manage apps, machines, secrets, and volumes
 //! ```
 //!
+//! Use [`FlyControl::builder`] to point the SDK at another endpoint, such as the
+//! internal Machines API reachable from inside a Fly organization's network:
+//!
+//! ```rust
+//! use fly_sdk::{FlyControl, INTERNAL_API_BASE_URL};
+//! use std::time::Duration;
+//!
+//! let fly_control = FlyControl::builder()
+//!     .api_token("your_api_token")
+//!     .base_url(INTERNAL_API_BASE_URL)
+//!     .timeout(Duration::from_secs(30))
+//!     .build()
+//!     .expect("valid configuration");
+//! ```
+//!
 //! The `FlyControl` struct is the main entry point for interacting with the Fly.io API.
 //! It encapsulates managers for applications, machines, volumes, and secrets, allowing
 //! you to manage these entities using a single unified interface.
@@ -38,23 +53,59 @@
 //! - `volumes`: Enable volume management.
 //! - `secrets`: Enable secret management.
 //! - `full`: Enable all features.
+//! - `blocking`: Enable the synchronous client in [`blocking`].
+//! - `testing`: Enable the in-memory fake Machines API in `testing`, for tests.
+//!
+//! # Errors
+//! Every manager method returns [`FlyError`], which distinguishes transport failures,
+//! undecodable responses and API errors (with the HTTP status, Fly's error message and
+//! the `fly-request-id` of the failed call).
+
+mod client;
+pub mod error;
+pub mod middleware;
+pub mod rate_limit;
+pub mod retry;
+pub mod token;
 
 #[cfg(feature = "apps")]
 pub mod apps;
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
 #[cfg(feature = "machines")]
 pub mod machines;
 
 #[cfg(feature = "secrets")]
 pub mod secrets;
 
+#[cfg(feature = "testing")]
+pub mod testing;
+
 #[cfg(feature = "volumes")]
 pub mod volumes;
 
-use reqwest::Client;
+pub use error::FlyError;
+pub use rate_limit::RateLimits;
+pub use retry::RetryPolicy;
+
+use client::ApiClient;
+use middleware::Middleware;
+use reqwest::{Client, Proxy};
+use std::sync::Arc;
+use std::time::Duration;
+use token::TokenProvider;
+
+/// Public Machines API endpoint used by default.
+pub const API_BASE_URL: &str = "https://api.machines.dev/v1";
 
-const API_BASE_URL: &str = "https://api.machines.dev/v1";
+/// Machines API endpoint reachable over a Fly organization's private network.
+pub const INTERNAL_API_BASE_URL: &str = "http://_api.internal:4280/v1";
 
+const USER_AGENT: &str = concat!("fly-sdk/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Clone)]
 pub struct FlyControl {
     #[cfg(feature = "apps")]
     pub apps: apps::AppManager,
@@ -71,19 +122,178 @@ pub struct FlyControl {
 
 impl FlyControl {
     pub fn new(api_token: String) -> Self {
-        let client = Client::new();
+        Self::from_api(ApiClient::new(
+            Client::new(),
+            Arc::new(token::StaticToken::new(&api_token)),
+        ))
+    }
+
+    /// Reads the token from `FLY_API_TOKEN` or `FLY_ACCESS_TOKEN`, falling back to the
+    /// token flyctl stores in `~/.fly/config.yml`.
+    ///
+    /// The token is looked up again for each request, so a token refreshed by flyctl is
+    /// picked up; this fails only if none is available right now.
+    pub fn from_env() -> Result<Self, FlyError> {
+        let provider = token::TokenChain::default();
+        provider.token()?;
+        Ok(Self::from_api(ApiClient::new(
+            Client::new(),
+            Arc::new(provider),
+        )))
+    }
+
+    pub fn builder() -> FlyControlBuilder {
+        FlyControlBuilder::new()
+    }
+
+    /// Every manager shares `api`, including its rate limiter and middleware.
+    #[allow(unused_variables)]
+    fn from_api(api: ApiClient) -> Self {
         FlyControl {
             #[cfg(feature = "apps")]
-            apps: apps::AppManager::new(client.clone(), api_token.clone()),
+            apps: apps::AppManager::from_api(api.clone()),
 
             #[cfg(feature = "machines")]
-            machines: machines::MachineManager::new(client.clone(), api_token.clone()),
+            machines: machines::MachineManager::from_api(api.clone()),
 
             #[cfg(feature = "volumes")]
-            volumes: volumes::VolumeManager::new(client.clone(), api_token.clone()),
+            volumes: volumes::VolumeManager::from_api(api.clone()),
 
             #[cfg(feature = "secrets")]
-            secrets: secrets::SecretsManager::new(client.clone(), api_token.clone()),
+            secrets: secrets::SecretsManager::from_api(api.clone()),
+        }
+    }
+}
+
+/// Builder for a [`FlyControl`] with a custom endpoint or HTTP client settings.
+///
+/// Timeouts, user agent and proxy are applied to the client the builder creates.
+/// They are ignored when a pre-built client is passed with [`FlyControlBuilder::client`].
+pub struct FlyControlBuilder {
+    token_provider: Option<Arc<dyn TokenProvider>>,
+    base_url: String,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    user_agent: String,
+    proxy: Option<Proxy>,
+    client: Option<Client>,
+    retry_policy: RetryPolicy,
+    rate_limits: RateLimits,
+    middleware: Vec<Arc<dyn Middleware>>,
+}
+
+impl Default for FlyControlBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlyControlBuilder {
+    pub fn new() -> Self {
+        Self {
+            token_provider: None,
+            base_url: API_BASE_URL.to_string(),
+            timeout: None,
+            connect_timeout: None,
+            user_agent: USER_AGENT.to_string(),
+            proxy: None,
+            client: None,
+            retry_policy: RetryPolicy::default(),
+            rate_limits: RateLimits::default(),
+            middleware: Vec::new(),
         }
     }
+
+    pub fn api_token(self, api_token: &str) -> Self {
+        self.token_provider(token::StaticToken::new(api_token))
+    }
+
+    /// Looks up the token for every request, e.g. from the environment or a rotating source.
+    pub fn token_provider(mut self, token_provider: impl TokenProvider + 'static) -> Self {
+        self.token_provider = Some(Arc::new(token_provider));
+        self
+    }
+
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.to_string();
+        self
+    }
+
+    /// Total time allowed for each request, from connecting until the body is read.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Uses a pre-built client instead of creating one.
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Client-side limits for Machines API calls, shared by every clone of the built `FlyControl`.
+    pub fn rate_limits(mut self, rate_limits: RateLimits) -> Self {
+        self.rate_limits = rate_limits;
+        self
+    }
+
+    /// Adds middleware that runs around every request; see [`middleware`].
+    pub fn middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    pub fn build(self) -> Result<FlyControl, FlyError> {
+        let token_provider = self.token_provider.ok_or_else(|| {
+            FlyError::Config("an API token or token provider is required".to_string())
+        })?;
+
+        let client = match self.client {
+            Some(client) => client,
+            None => {
+                let mut builder = Client::builder().user_agent(self.user_agent);
+                if let Some(timeout) = self.timeout {
+                    builder = builder.timeout(timeout);
+                }
+                if let Some(connect_timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(connect_timeout);
+                }
+                if let Some(proxy) = self.proxy {
+                    builder = builder.proxy(proxy);
+                }
+                builder.build()?
+            }
+        };
+
+        let mut api = ApiClient::new(client, token_provider);
+        api.set_base_url(&self.base_url);
+        api.set_retry_policy(self.retry_policy);
+        // one limiter for the whole FlyControl, so clones share the same buckets
+        api.set_rate_limiter(rate_limit::RateLimiter::new(self.rate_limits));
+        for middleware in self.middleware {
+            api.push_middleware(middleware);
+        }
+
+        Ok(FlyControl::from_api(api))
+    }
 }
@@ -1,152 +1,582 @@
+use crate::client::{ApiClient, ApiRequest};
+use crate::error::FlyError;
+use crate::machines::bulk::{CreateManyOptions, CreateManyReport, Replicas};
+use crate::machines::clone::CloneOptions;
+use crate::machines::deploy::{self, DeployReport, RollingDeploy};
+use crate::machines::drain::cpu_usage;
+use crate::machines::events::{self, MachineEvent, WatchOptions};
+use crate::machines::exec::{ExecOutput, ExecRequest, ExecResponse};
+use crate::machines::lease::{LeaseRequest, LeaseResponse, LEASE_NONCE_HEADER};
+use crate::machines::query::ListMachinesQuery;
+use crate::machines::signal::{SignalRequest, StopRequest};
 use crate::machines::{
-    CommandResponse, EventResponse, MachineRequest, MachineResponse, MachineState, ProcessResponse,
+    CommandResponse, DrainOptions, EventResponse, Lease, LeaseGuard, MachineConfig,
+    MachineLifecycleState, MachineRegions, MachineRequest, MachineResponse, MachineSize,
+    MachineState, ProcessResponse, Signal, StartResponse, StopOptions,
 };
-use crate::API_BASE_URL;
-use reqwest::Client;
-use std::error::Error;
-use tracing::debug;
+use crate::middleware::Middleware;
+use crate::rate_limit::{RateLimitAction, RateLimiter, RateLimits};
+use crate::retry::RetryPolicy;
+use crate::token::{StaticToken, TokenProvider};
+#[cfg(feature = "volumes")]
+use crate::volumes::{CreateVolumeRequest, VolumeManager};
+use futures_util::stream::{self, Stream, StreamExt};
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{debug, warn};
 
+/// Longest wait the Machines API accepts in a single call, in seconds.
+const MAX_WAIT_SECONDS: u64 = 60;
+
+const DEFAULT_WAIT_SECONDS: u64 = 60;
+
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+const EXEC_TIMEOUT_MARGIN: Duration = Duration::from_secs(10);
+
+/// Grace period Fly gives a stopping machine without a `stop_config` timeout.
+const DEFAULT_STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Extra time a stopping machine gets beyond its grace period.
+const STOP_TIMEOUT_MARGIN: Duration = Duration::from_secs(10);
+
+/// Extra time a wait request may take beyond the timeout passed to the API.
+const WAIT_TIMEOUT_MARGIN: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
 pub struct MachineManager {
-    client: Client,
-    api_token: String,
+    api: ApiClient,
+    lease_nonce: Option<String>,
 }
 
 impl MachineManager {
     pub fn new(client: Client, api_token: String) -> Self {
-        Self { client, api_token }
+        Self::from_api(ApiClient::new(
+            client,
+            Arc::new(StaticToken::new(&api_token)),
+        ))
+    }
+
+    pub(crate) fn from_api(api: ApiClient) -> Self {
+        Self {
+            api,
+            lease_nonce: None,
+        }
+    }
+
+    /// Looks up the API token from `token_provider` for every request.
+    pub fn with_token_provider(mut self, token_provider: impl TokenProvider + 'static) -> Self {
+        self.api.set_token_provider(Arc::new(token_provider));
+        self
+    }
+
+    /// Sends requests to `base_url` instead of [`API_BASE_URL`](crate::API_BASE_URL).
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.api.set_base_url(base_url);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.api.set_retry_policy(retry_policy);
+        self
+    }
+
+    /// Throttles this manager's requests with its own limiter using `rate_limits`.
+    pub fn with_rate_limits(mut self, rate_limits: RateLimits) -> Self {
+        self.api.set_rate_limiter(RateLimiter::new(rate_limits));
+        self
+    }
+
+    pub fn with_middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.api.push_middleware(Arc::new(middleware));
+        self
+    }
+
+    /// Sends the nonce of `lease` with every mutating call, so they succeed while the
+    /// lease is held.
+    pub fn with_lease(&self, lease: &LeaseGuard) -> Self {
+        self.with_lease_nonce(&lease.nonce())
+    }
+
+    /// Like [`MachineManager::with_lease`], for a lease managed by hand.
+    pub fn with_lease_nonce(&self, nonce: &str) -> Self {
+        let mut machines = self.clone();
+        machines.lease_nonce = Some(nonce.to_string());
+        machines
+    }
+
+    /// Attaches the lease nonce, if any, to a mutating request.
+    fn leased(&self, request: ApiRequest) -> ApiRequest {
+        match &self.lease_nonce {
+            Some(nonce) => request.header(LEASE_NONCE_HEADER, nonce),
+            None => request,
+        }
     }
 
     pub async fn create(
         &self,
         app_name: &str,
         request_data: MachineRequest,
-    ) -> Result<MachineResponse, Box<dyn Error>> {
+    ) -> Result<MachineResponse, FlyError> {
         debug!("Creating machine for app: {}", app_name);
-        let url = format!("{}/apps/{}/machines", API_BASE_URL, app_name);
-
         debug!("Request data: {:#?}", request_data);
-        let response = self
-            .client
-            .post(&url)
-            .bearer_auth(&self.api_token)
-            .header("Content-Type", "application/json")
+        let request = ApiRequest::post("/apps/{app}/machines")
+            .param("app", app_name)
             .json(&request_data)
-            .send()
-            .await?;
+            .action(RateLimitAction::Create);
 
-        debug!("Response: {:#?}", response);
-        if response.status().is_success() {
-            let response_text = response.text().await?;
-            debug!("Raw JSON response body: {}", response_text);
+        self.api.json(request).await
+    }
 
-            let response_body: MachineResponse = serde_json::from_str(&response_text)?;
-            Ok(response_body)
-        } else {
-            Err(format!("Request failed with status: {}", response.status()).into())
+    /// Lists the app's machines that match `query`.
+    pub async fn list_with(
+        &self,
+        app_name: &str,
+        query: ListMachinesQuery,
+    ) -> Result<Vec<MachineResponse>, FlyError> {
+        debug!("Listing machines of app {} matching {:?}", app_name, query);
+        let mut request = ApiRequest::get("/apps/{app}/machines")
+            .param("app", app_name)
+            .action(RateLimitAction::Read);
+        for (name, value) in query.params() {
+            request = request.query(&name, value);
         }
+
+        let machines: Vec<MachineResponse> = self.api.json(request).await?;
+        Ok(machines
+            .into_iter()
+            .filter(|machine| query.matches(machine))
+            .collect())
+    }
+
+    /// Changes the machine's CPUs and memory to `size`, keeping the rest of its config, and
+    /// restarts it on the new size. A stopped machine stays stopped.
+    ///
+    /// A [`Preset`](crate::machines::Preset) replaces the whole guest config, clearing any
+    /// GPUs. With a [`GuestConfig`](crate::machines::GuestConfig), any field left as `None`,
+    /// such as the CPU kind when only raising the memory, keeps its current value. The
+    /// resulting size is checked with
+    /// [`GuestConfig::validate`](crate::machines::GuestConfig::validate) before anything is
+    /// sent.
+    pub async fn resize(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+        size: impl Into<MachineSize>,
+    ) -> Result<MachineResponse, FlyError> {
+        let size = size.into();
+        let machine = self.get_machine(app_name, machine_id).await?;
+        let mut config = machine.config.ok_or_else(|| {
+            FlyError::Config(format!("machine {} has no config to resize", machine_id))
+        })?;
+        let guest = size.apply(config.guest.take().unwrap_or_default());
+        guest.validate()?;
+        debug!("Resizing machine {} to {:?}", machine_id, guest);
+        config.guest = Some(guest);
+
+        let instance_id = machine.instance_id.unwrap_or_default();
+        let running = machine
+            .state
+            .as_ref()
+            .is_some_and(|state| state.is_running() || *state == MachineLifecycleState::Starting);
+        let request = MachineRequest::new(config, machine.name, parse_region(machine.region))
+            .with_skip_launch(!running);
+        self.update_machine(app_name, machine_id, &instance_id, request)
+            .await
+    }
+
+    /// Creates one machine per replica from `template`, at most `options.concurrency` at a
+    /// time, and reports how each creation went instead of stopping at the first failure.
+    ///
+    /// A named template gets `-1`, `-2`, ... appended so the names stay unique.
+    pub async fn create_many(
+        &self,
+        app_name: &str,
+        template: &MachineRequest,
+        replicas: impl Into<Replicas>,
+        options: CreateManyOptions,
+    ) -> Result<CreateManyReport, FlyError> {
+        if options.concurrency == 0 {
+            return Err(FlyError::Config(
+                "create_many needs a concurrency of at least 1".to_string(),
+            ));
+        }
+        let regions = replicas.into().regions();
+        debug!("Creating {} machines for app: {}", regions.len(), app_name);
+
+        let failures = AtomicUsize::new(0);
+        let too_many_failures = || {
+            options
+                .rollback_after
+                .is_some_and(|limit| failures.load(Ordering::SeqCst) > limit)
+        };
+        let mut results: Vec<_> = stream::iter(regions.into_iter().enumerate())
+            .map(|(index, region)| {
+                let mut request = template.clone();
+                if region.is_some() {
+                    request.region = region;
+                }
+                if let Some(name) = &template.name {
+                    request.name = Some(format!("{}-{}", name, index + 1));
+                }
+                let (failures, too_many_failures) = (&failures, &too_many_failures);
+                async move {
+                    if too_many_failures() {
+                        return (index, None);
+                    }
+                    let result = self.create(app_name, request).await;
+                    if result.is_err() {
+                        failures.fetch_add(1, Ordering::SeqCst);
+                    }
+                    (index, Some(result))
+                }
+            })
+            .buffer_unordered(options.concurrency)
+            .collect()
+            .await;
+        results.sort_by_key(|(index, _)| *index);
+        let results: Vec<_> = results.into_iter().map(|(_, result)| result).collect();
+
+        let rolled_back = too_many_failures();
+        if rolled_back {
+            debug!("Too many machines failed to create, destroying the others");
+            let created = results
+                .iter()
+                .flatten()
+                .filter_map(|result| result.as_ref().ok()?.id.as_deref());
+            stream::iter(created)
+                .for_each_concurrent(options.concurrency, |machine_id| async move {
+                    if let Err(err) = self.delete(app_name, machine_id, true).await {
+                        warn!("Failed to roll back machine {}: {}", machine_id, err);
+                    }
+                })
+                .await;
+        }
+
+        Ok(CreateManyReport {
+            results,
+            rolled_back,
+        })
     }
 
-    pub async fn list(&self, app_name: &str) -> Result<Vec<MachineResponse>, Box<dyn Error>> {
-        let url = format!("{}/apps/{}/machines", API_BASE_URL, app_name);
+    /// Creates a new machine from the config of `source_id`, changed as `options` says.
+    ///
+    /// Volumes created for the clone are destroyed again if the machine can't be created.
+    pub async fn clone_machine(
+        &self,
+        app_name: &str,
+        source_id: &str,
+        options: CloneOptions,
+    ) -> Result<MachineResponse, FlyError> {
+        debug!("Cloning machine {}", source_id);
+        let source = self.get_machine(app_name, source_id).await?;
+        let mut config = source.config.ok_or_else(|| {
+            FlyError::Config(format!("machine {} has no config to clone", source_id))
+        })?;
+        let region = options.region.or_else(|| parse_region(source.region));
+        if let Some(overrides) = options.config_overrides {
+            overrides(&mut config);
+        }
+
+        let mut new_volumes = Vec::new();
+        if config
+            .mounts
+            .as_ref()
+            .is_some_and(|mounts| !mounts.is_empty())
+        {
+            if !options.attach_new_volume {
+                return Err(FlyError::Config(format!(
+                    "machine {} has volumes; set attach_new_volume to give the clone its own",
+                    source_id
+                )));
+            }
+            let Some(volume_region) = region.clone() else {
+                return Err(FlyError::Config(
+                    "a region is needed to create the clone's volumes".to_string(),
+                ));
+            };
+            for mount in config.mounts.iter_mut().flatten() {
+                match self
+                    .create_clone_volume(app_name, &mount.volume, volume_region.clone())
+                    .await
+                {
+                    Ok(volume_id) => {
+                        mount.volume = volume_id.clone();
+                        new_volumes.push(volume_id);
+                    }
+                    Err(err) => {
+                        self.destroy_volumes(app_name, &new_volumes).await;
+                        return Err(err);
+                    }
+                }
+            }
+        }
+
+        let request = MachineRequest::new(config, options.name, region);
+        let result = self.create(app_name, request).await;
+        if result.is_err() {
+            self.destroy_volumes(app_name, &new_volumes).await;
+        }
+        result
+    }
 
-        let response = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.api_token)
-            .send()
+    /// Creates an empty volume shaped like `source_volume_id`, returning its id.
+    #[cfg(feature = "volumes")]
+    async fn create_clone_volume(
+        &self,
+        app_name: &str,
+        source_volume_id: &str,
+        region: MachineRegions,
+    ) -> Result<String, FlyError> {
+        let volumes = VolumeManager::from_api(self.api.clone());
+        let source = volumes.get_volume(app_name, source_volume_id).await?;
+
+        let name = source.name.unwrap_or_else(|| "data".to_string());
+        let mut volume_request =
+            CreateVolumeRequest::builder(&name, region, source.size_gb.unwrap_or(1))
+                .encrypted(source.encrypted.unwrap_or(true));
+        if let Some(fstype) = source.fstype {
+            volume_request = volume_request.fstype(fstype);
+        }
+        let volume = volumes
+            .create_volume(app_name, volume_request.build())
             .await?;
+        debug!("Created volume {:?} for clone", volume.id);
 
-        if response.status() == reqwest::StatusCode::OK {
-            let response_text = response.text().await?;
-            debug!("Raw JSON response body: {}", response_text);
-            let machines: Vec<MachineResponse> = serde_json::from_str(&response_text)?;
-            debug!("List of machines: {:?}", machines);
-            Ok(machines)
-        } else {
-            debug!("Failed to list machines: {:?}", response.status());
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Failed to list machines",
-            )))
+        volume
+            .id
+            .ok_or_else(|| FlyError::Config(format!("volume {} was created without an id", name)))
+    }
+
+    #[cfg(not(feature = "volumes"))]
+    async fn create_clone_volume(
+        &self,
+        _app_name: &str,
+        _source_volume_id: &str,
+        _region: MachineRegions,
+    ) -> Result<String, FlyError> {
+        Err(FlyError::Config(
+            "cloning a machine with volumes needs the `volumes` feature".to_string(),
+        ))
+    }
+
+    #[cfg(feature = "volumes")]
+    async fn destroy_volumes(&self, app_name: &str, volume_ids: &[String]) {
+        let volumes = VolumeManager::from_api(self.api.clone());
+        for volume_id in volume_ids {
+            if let Err(err) = volumes.destroy_volume(app_name, volume_id).await {
+                warn!("Failed to destroy volume {}: {}", volume_id, err);
+            }
         }
     }
 
+    /// Without the `volumes` feature no volumes are ever created for a clone.
+    #[cfg(not(feature = "volumes"))]
+    async fn destroy_volumes(&self, _app_name: &str, _volume_ids: &[String]) {}
+
+    /// Applies `update` to the config of each of the app's machines, `max_unavailable`
+    /// machines at a time, waiting for every updated machine to start with all its checks
+    /// passing before moving on to the next batch. Stopped machines are updated but stay
+    /// stopped.
+    ///
+    /// If a batch fails, no further batches are started and every machine updated so far
+    /// is put back on its previous config before the error is returned.
+    pub async fn deploy(
+        &self,
+        app_name: &str,
+        update: impl Fn(&mut MachineConfig) + Send + Sync,
+        strategy: RollingDeploy,
+    ) -> Result<DeployReport, FlyError> {
+        debug!("Deploying app {} with {:?}", app_name, strategy);
+        deploy::rolling(self, app_name, &update, &strategy).await
+    }
+
+    pub async fn list(&self, app_name: &str) -> Result<Vec<MachineResponse>, FlyError> {
+        let request = ApiRequest::get("/apps/{app}/machines")
+            .param("app", app_name)
+            .action(RateLimitAction::Read);
+
+        let machines: Vec<MachineResponse> = self.api.json(request).await?;
+        debug!("List of machines: {:?}", machines);
+        Ok(machines)
+    }
+
     pub async fn stop(
         &self,
         app_name: &str,
         machine_id: &str,
         instance_id: &str,
-    ) -> Result<(), Box<dyn Error>> {
-        debug!("Stopping machine {}", machine_id);
-        let url = format!(
-            "{}/apps/{}/machines/{}/stop",
-            API_BASE_URL, app_name, machine_id
-        );
-
-        let response = self
-            .client
-            .post(&url)
-            .bearer_auth(&self.api_token)
-            .send()
+    ) -> Result<(), FlyError> {
+        self.send_stop(app_name, machine_id, &StopOptions::default())
             .await?;
 
-        if response.status() == reqwest::StatusCode::OK {
-            debug!("Stopped machine {}", machine_id);
+        self.wait_for_machine_state(
+            app_name,
+            machine_id,
+            MachineState::Stopped,
+            None,
+            Some(instance_id),
+        )
+        .await?;
 
-            self.wait_for_machine_state(
-                app_name,
-                machine_id,
-                MachineState::Stopped,
-                None,
-                Some(instance_id),
-            )
-            .await?;
+        Ok(())
+    }
 
-            Ok(())
-        } else {
-            debug!(
-                "Failed to stop machine {}: {:?}",
-                machine_id,
-                response.status()
-            );
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Failed to stop machine",
-            )))
+    /// Stops the machine with a specific signal and grace period, and waits until it's
+    /// `stopped`. Without `options.timeout`, the wait follows the grace period of the
+    /// machine's `stop_config`, or Fly's default of 5 seconds.
+    ///
+    /// ```rust,no_run
+    /// # use fly_sdk::machines::{MachineManager, Signal, StopOptions};
+    /// # use std::time::Duration;
+    /// # async fn run(machines: MachineManager) -> Result<(), fly_sdk::FlyError> {
+    /// let options = StopOptions {
+    ///     signal: Some(Signal::SIGINT),
+    ///     timeout: Some(Duration::from_secs(30)),
+    /// };
+    /// machines.stop_with("my-app", "3d8d9014b32d38", options).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn stop_with(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+        options: StopOptions,
+    ) -> Result<(), FlyError> {
+        let grace_period = match options.timeout {
+            Some(timeout) => timeout,
+            None => self.stop_timeout(app_name, machine_id).await?,
+        };
+        self.send_stop(app_name, machine_id, &options).await?;
+
+        // leave the machine its whole grace period, plus a little to shut down
+        self.wait_for_states(
+            app_name,
+            machine_id,
+            &[MachineState::Stopped],
+            grace_period + STOP_TIMEOUT_MARGIN,
+            None,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// The grace period set in the machine's `stop_config`, or Fly's default.
+    async fn stop_timeout(&self, app_name: &str, machine_id: &str) -> Result<Duration, FlyError> {
+        let machine = self.get_machine(app_name, machine_id).await?;
+        let seconds = machine
+            .config
+            .and_then(|config| config.stop_config)
+            .and_then(|stop_config| stop_config.timeout)
+            .map(|timeout| timeout.duration);
+        Ok(seconds.map_or(DEFAULT_STOP_TIMEOUT, Duration::from_secs))
+    }
+
+    async fn send_stop(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+        options: &StopOptions,
+    ) -> Result<(), FlyError> {
+        debug!("Stopping machine {}", machine_id);
+        let mut request = ApiRequest::post("/apps/{app}/machines/{machine}/stop")
+            .param("app", app_name)
+            .param("machine", machine_id)
+            .idempotent()
+            .action(RateLimitAction::StartStop);
+        if options.signal.is_some() || options.timeout.is_some() {
+            request = request.json(&StopRequest::from(options));
         }
+
+        self.api.execute(self.leased(request)).await?;
+        debug!("Stopped machine {}", machine_id);
+        Ok(())
     }
 
-    pub async fn start(&self, app_name: &str, machine_id: &str) -> Result<(), Box<dyn Error>> {
+    /// Sends `signal` to the machine's main process, e.g. `SIGHUP` to reload its config.
+    pub async fn signal(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+        signal: Signal,
+    ) -> Result<(), FlyError> {
+        debug!("Sending {} to machine {}", signal, machine_id);
+        let request = self.leased(
+            ApiRequest::post("/apps/{app}/machines/{machine}/signal")
+                .param("app", app_name)
+                .param("machine", machine_id)
+                .idempotent()
+                .json(&SignalRequest { signal })
+                .action(RateLimitAction::StartStop),
+        );
+
+        self.api.execute(request).await
+    }
+
+    /// Starts the machine and waits until it's running.
+    ///
+    /// Suspended machines resume from their snapshot, which
+    /// [`StartResponse::resumed_from_snapshot`] reports.
+    pub async fn start(&self, app_name: &str, machine_id: &str) -> Result<StartResponse, FlyError> {
         debug!("Starting machine {}", machine_id);
-        let url = format!(
-            "{}/apps/{}/machines/{}/start",
-            API_BASE_URL, app_name, machine_id
+        let request = self.leased(
+            ApiRequest::post("/apps/{app}/machines/{machine}/start")
+                .param("app", app_name)
+                .param("machine", machine_id)
+                .idempotent()
+                .action(RateLimitAction::StartStop),
         );
 
-        let response = self
-            .client
-            .post(&url)
-            .bearer_auth(&self.api_token)
-            .send()
+        let start_response: StartResponse = self.api.json(request).await?;
+        debug!(
+            "Started machine {} from state {:?}",
+            machine_id, start_response.previous_state
+        );
+        self.wait_for_machine_state(app_name, machine_id, MachineState::Started, None, None)
             .await?;
 
-        if response.status() == reqwest::StatusCode::OK {
-            debug!("Started machine {}", machine_id);
-            self.wait_for_machine_state(app_name, machine_id, MachineState::Started, None, None)
-                .await?;
+        Ok(start_response)
+    }
 
-            Ok(())
-        } else {
-            debug!(
-                "Failed to start machine {}: {:?}",
-                machine_id,
-                response.status()
-            );
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Failed to start machine",
-            )))
-        }
+    /// Snapshots the machine's memory and stops it, waiting until it's `suspended`.
+    ///
+    /// A later [`MachineManager::start`] resumes from the snapshot, usually well under a
+    /// second.
+    pub async fn suspend(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+        instance_id: &str,
+    ) -> Result<(), FlyError> {
+        debug!("Suspending machine {}", machine_id);
+        let request = self.leased(
+            ApiRequest::post("/apps/{app}/machines/{machine}/suspend")
+                .param("app", app_name)
+                .param("machine", machine_id)
+                .idempotent()
+                .action(RateLimitAction::StartStop),
+        );
+
+        self.api.execute(request).await?;
+        debug!("Suspended machine {}", machine_id);
+
+        self.wait_for_machine_state(
+            app_name,
+            machine_id,
+            MachineState::Suspended,
+            None,
+            Some(instance_id),
+        )
+        .await?;
+
+        Ok(())
     }
 
     pub async fn delete(
@@ -154,40 +584,28 @@ impl MachineManager {
         app_name: &str,
         machine_id: &str,
         force: bool,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), FlyError> {
         debug!("Deleting machine {}", machine_id);
-        let mut url = format!("{}/apps/{}/machines/{}", API_BASE_URL, app_name, machine_id);
-
+        let mut request = self.leased(
+            ApiRequest::delete("/apps/{app}/machines/{machine}")
+                .param("app", app_name)
+                .param("machine", machine_id)
+                .action(RateLimitAction::Other),
+        );
         if force {
-            url.push_str("?force=true");
+            request = request.query("force", true);
         }
 
-        let response = self
-            .client
-            .delete(&url)
-            .bearer_auth(&self.api_token)
-            .send()
+        self.api.execute(request).await?;
+        debug!("Deleted machine {}", machine_id);
+        self.wait_for_machine_state(app_name, machine_id, MachineState::Destroyed, None, None)
             .await?;
 
-        if response.status() == reqwest::StatusCode::OK {
-            debug!("Deleted machine {}", machine_id);
-            self.wait_for_machine_state(app_name, machine_id, MachineState::Destroyed, None, None)
-                .await?;
-
-            Ok(())
-        } else {
-            debug!(
-                "Failed to delete machine {}: {:?}",
-                machine_id,
-                response.status()
-            );
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Failed to delete machine",
-            )))
-        }
+        Ok(())
     }
 
+    /// Waits for a single state; see [`MachineManager::wait_for_states`]. `timeout` is
+    /// in seconds and defaults to 60.
     pub async fn wait_for_machine_state(
         &self,
         app_name: &str,
@@ -195,82 +613,180 @@ impl MachineManager {
         desired_state: MachineState,
         timeout: Option<u64>,
         instance_id: Option<&str>,
-    ) -> Result<MachineResponse, Box<dyn Error>> {
+    ) -> Result<MachineResponse, FlyError> {
+        let timeout = Duration::from_secs(timeout.unwrap_or(DEFAULT_WAIT_SECONDS));
+        self.wait_for_states(app_name, machine_id, &[desired_state], timeout, instance_id)
+            .await
+    }
+
+    /// Waits until the machine is in any of `states`, returning the machine as last seen.
+    ///
+    /// A single state is awaited with the API's wait endpoint, re-issued as often as
+    /// needed since each call is capped at 60 seconds; calls that are throttled or fail
+    /// on Fly's side are retried with backoff until `timeout`. Several states, or states the
+    /// endpoint doesn't support such as [`MachineState::Failed`], are polled instead.
+    /// With `instance_id`, only that version of the machine counts.
+    ///
+    /// Fails with [`FlyError::Timeout`], carrying the last observed state, once `timeout`
+    /// has passed.
+    pub async fn wait_for_states(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+        states: &[MachineState],
+        timeout: Duration,
+        instance_id: Option<&str>,
+    ) -> Result<MachineResponse, FlyError> {
         debug!(
-            "Waiting for machine {} to reach state: {}",
-            machine_id, desired_state
-        );
-        let url = format!(
-            "{}/apps/{}/machines/{}/wait",
-            API_BASE_URL, app_name, machine_id
+            "Waiting up to {:?} for machine {} to reach state: {:?}",
+            timeout, machine_id, states
         );
+        let started_at = Instant::now();
+        let deadline = started_at + timeout;
+        let server_wait = match states {
+            [state] if state.is_waitable() => Some(*state),
+            _ => None,
+        };
+        let mut poll_interval = Duration::from_millis(200);
 
-        let mut query_params = vec![("state", desired_state.to_string())];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let mut wait_failed = false;
+            if let Some(state) = server_wait.filter(|_| !remaining.is_zero()) {
+                match self
+                    .wait_once(app_name, machine_id, state, remaining, instance_id)
+                    .await
+                {
+                    Ok(()) => return self.get_machine(app_name, machine_id).await,
+                    Err(err) if err.status() == Some(StatusCode::REQUEST_TIMEOUT) => {}
+                    // throttled or a hiccup on Fly's side: back off and wait again
+                    Err(err) if is_transient(&err) => {
+                        debug!(
+                            "Waiting for machine {} failed, retrying: {}",
+                            machine_id, err
+                        );
+                        wait_failed = true;
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
 
-        if let Some(timeout_value) = timeout {
-            query_params.push(("timeout", timeout_value.to_string()));
-        }
+            let machine = self.get_machine(app_name, machine_id).await?;
+            let reached = states.iter().any(|state| {
+                machine
+                    .state
+                    .as_ref()
+                    .is_some_and(|current| current == state)
+            }) && instance_id
+                .is_none_or(|id| machine.instance_id.as_deref() == Some(id));
+            if reached {
+                return Ok(machine);
+            }
 
-        if let Some(instance_id_value) = instance_id {
-            query_params.push(("instance_id", instance_id_value.to_string()));
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                let expected = states
+                    .iter()
+                    .map(|state| state.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" or ");
+                return Err(FlyError::Timeout {
+                    machine_id: machine_id.to_string(),
+                    expected,
+                    last_state: machine.state.map(String::from),
+                    waited: started_at.elapsed(),
+                });
+            }
+            if server_wait.is_none() || wait_failed {
+                tokio::time::sleep(poll_interval.min(remaining)).await;
+                poll_interval = (poll_interval * 2).min(MAX_POLL_INTERVAL);
+            }
         }
+    }
 
-        let response = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.api_token)
-            .query(&query_params)
-            .send()
-            .await?;
+    /// One call to the wait endpoint, which answers 408 if `timeout` passes first.
+    async fn wait_once(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+        state: MachineState,
+        timeout: Duration,
+        instance_id: Option<&str>,
+    ) -> Result<(), FlyError> {
+        // the endpoint takes whole seconds, up to a minute
+        let timeout_secs = (timeout.as_secs_f64().ceil() as u64).clamp(1, MAX_WAIT_SECONDS);
+        let mut request = ApiRequest::get("/apps/{app}/machines/{machine}/wait")
+            .param("app", app_name)
+            .param("machine", machine_id)
+            .query("state", state)
+            .query("timeout", timeout_secs)
+            .action(RateLimitAction::Read)
+            // the server holds the request, so it may outlast the client-wide timeout
+            .timeout(Duration::from_secs(timeout_secs) + WAIT_TIMEOUT_MARGIN)
+            // a 408 only means this call's timeout passed; the caller decides whether to retry
+            .without_retry();
 
-        if response.status().is_success() {
-            let wait_for_state_response: MachineResponse = response.json().await?;
-            Ok(wait_for_state_response)
-        } else {
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Failed to wait for state: {:?}", response.status()),
-            )))
+        if let Some(instance_id_value) = instance_id {
+            request = request.query("instance_id", instance_id_value);
         }
+
+        self.api.execute(request).await
     }
 
+    /// Replaces the machine's config, provided it's still at `instance_id`, and waits for
+    /// the new instance to be started, or stopped if the request sets `skip_launch`.
+    ///
+    /// Fails with [`FlyError::Conflict`] if the machine was updated since `instance_id`
+    /// was read.
     pub async fn update_machine(
         &self,
         app_name: &str,
         machine_id: &str,
-        #[allow(unused_variables)] instance_id: &str,
+        instance_id: &str,
         machine_request: MachineRequest,
-    ) -> Result<MachineResponse, Box<dyn Error>> {
-        debug!("Updating machine {}", machine_id);
-        let url = format!("{}/apps/{}/machines/{}", API_BASE_URL, app_name, machine_id);
-
-        let response = self
-            .client
-            .post(&url)
-            .bearer_auth(&self.api_token)
-            .json(&machine_request)
-            .send()
+    ) -> Result<MachineResponse, FlyError> {
+        let desired_state = if machine_request.skip_launch == Some(true) {
+            MachineState::Stopped
+        } else {
+            MachineState::Started
+        };
+        let machine_response = self
+            .send_update(app_name, machine_id, instance_id, machine_request)
             .await?;
 
-        if response.status().is_success() {
-            let machine_response: MachineResponse = response.json().await?;
+        self.wait_for_states(
+            app_name,
+            machine_id,
+            &[desired_state],
+            Duration::from_secs(DEFAULT_WAIT_SECONDS),
+            machine_response.instance_id.as_deref(),
+        )
+        .await
+    }
 
-            // self.wait_for_machine_state(
-            //     app_name,
-            //     machine_id,
-            //     MachineState::Started,
-            //     None,
-            //     Some(instance_id),
-            // )
-            // .await?;
+    /// Sends the update of [`MachineManager::update_machine`] without waiting for the new
+    /// instance.
+    pub(crate) async fn send_update(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+        instance_id: &str,
+        mut machine_request: MachineRequest,
+    ) -> Result<MachineResponse, FlyError> {
+        debug!(
+            "Updating machine {} from instance {}",
+            machine_id, instance_id
+        );
+        machine_request.current_version = Some(instance_id.to_string());
+        let request = self.leased(
+            ApiRequest::post("/apps/{app}/machines/{machine}")
+                .param("app", app_name)
+                .param("machine", machine_id)
+                .json(&machine_request)
+                .action(RateLimitAction::Update),
+        );
 
-            Ok(machine_response)
-        } else {
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Failed to update machine: {:?}", response.status()),
-            )))
-        }
+        self.api.json(request).await
     }
 
     pub async fn restart_machine(
@@ -278,95 +794,79 @@ impl MachineManager {
         app_name: &str,
         machine_id: &str,
         instance_id: &str,
-    ) -> Result<MachineResponse, Box<dyn Error>> {
+    ) -> Result<MachineResponse, FlyError> {
         debug!("Restarting machine {}", machine_id);
-        let url = format!(
-            "{}/apps/{}/machines/{}/restart",
-            API_BASE_URL, app_name, machine_id
+        let request = self.leased(
+            ApiRequest::post("/apps/{app}/machines/{machine}/restart")
+                .param("app", app_name)
+                .param("machine", machine_id)
+                .idempotent()
+                .action(RateLimitAction::StartStop),
         );
 
-        let response = self
-            .client
-            .post(&url) // POST for restarting a machine
-            .bearer_auth(&self.api_token)
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            let machine_response: MachineResponse = response.json().await?;
+        let machine_response: MachineResponse = self.api.json(request).await?;
 
-            self.wait_for_machine_state(
-                app_name,
-                machine_id,
-                MachineState::Started,
-                None,
-                Some(instance_id),
-            )
-            .await?;
+        self.wait_for_machine_state(
+            app_name,
+            machine_id,
+            MachineState::Started,
+            None,
+            Some(instance_id),
+        )
+        .await?;
 
-            Ok(machine_response)
-        } else {
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Failed to restart machine: {:?}", response.status()),
-            )))
-        }
+        Ok(machine_response)
     }
 
     pub async fn list_events(
         &self,
         app_name: &str,
         machine_id: &str,
-    ) -> Result<Vec<EventResponse>, Box<dyn Error>> {
-        let url = format!(
-            "{}/apps/{}/machines/{}/events",
-            API_BASE_URL, app_name, machine_id
-        );
+    ) -> Result<Vec<EventResponse>, FlyError> {
+        let request = ApiRequest::get("/apps/{app}/machines/{machine}/events")
+            .param("app", app_name)
+            .param("machine", machine_id)
+            .action(RateLimitAction::Read);
 
-        let response = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.api_token)
-            .send()
-            .await?;
+        self.api.json(request).await
+    }
 
-        if response.status().is_success() {
-            let events: Vec<EventResponse> = response.json().await?;
-            Ok(events)
-        } else {
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Failed to list machine events: {:?}", response.status()),
-            )))
-        }
+    /// Streams the machine's events from now on, oldest first, as they happen; see
+    /// [`MachineManager::watch_events_with`].
+    pub async fn watch_events(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+    ) -> Result<impl Stream<Item = Result<MachineEvent, FlyError>> + Send + 'static, FlyError> {
+        self.watch_events_with(app_name, machine_id, WatchOptions::default())
+            .await
     }
 
-    pub async fn list_processes(
+    /// Lists the machine's events once, then polls for new ones and yields each once.
+    ///
+    /// Failed polls are yielded as errors and polling carries on, except when the machine
+    /// is gone. The stream ends after the machine's `destroy` event.
+    pub async fn watch_events_with(
         &self,
         app_name: &str,
         machine_id: &str,
-    ) -> Result<Vec<ProcessResponse>, Box<dyn Error>> {
-        let url = format!(
-            "{}/apps/{}/machines/{}/ps",
-            API_BASE_URL, app_name, machine_id
-        );
+        options: WatchOptions,
+    ) -> Result<impl Stream<Item = Result<MachineEvent, FlyError>> + Send + 'static, FlyError> {
+        debug!("Watching events of machine {}", machine_id);
+        events::watch(self.clone(), app_name, machine_id, options).await
+    }
 
-        let response = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.api_token)
-            .send()
-            .await?;
+    pub async fn list_processes(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+    ) -> Result<Vec<ProcessResponse>, FlyError> {
+        let request = ApiRequest::get("/apps/{app}/machines/{machine}/ps")
+            .param("app", app_name)
+            .param("machine", machine_id)
+            .action(RateLimitAction::Read);
 
-        if response.status().is_success() {
-            let processes: Vec<ProcessResponse> = response.json().await?;
-            Ok(processes)
-        } else {
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Failed to list processes: {:?}", response.status()),
-            )))
-        }
+        self.api.json(request).await
     }
 
     pub async fn execute_command(
@@ -375,15 +875,11 @@ impl MachineManager {
         machine_id: &str,
         command: Vec<&str>,
         timeout: Option<u64>,
-    ) -> Result<CommandResponse, Box<dyn Error>> {
+    ) -> Result<CommandResponse, FlyError> {
         debug!(
             "Executing command on machine {} with command: {:?}",
             machine_id, command
         );
-        let url = format!(
-            "{}/apps/{}/machines/{}/exec",
-            API_BASE_URL, app_name, machine_id
-        );
 
         let mut body = serde_json::json!({
             "command": command,
@@ -392,48 +888,305 @@ impl MachineManager {
             body["timeout"] = serde_json::json!(timeout_value);
         }
 
-        let response = self
-            .client
-            .post(&url)
-            .bearer_auth(&self.api_token)
+        let request = ApiRequest::post("/apps/{app}/machines/{machine}/exec")
+            .param("app", app_name)
+            .param("machine", machine_id)
             .json(&body)
-            .send()
-            .await?;
+            .action(RateLimitAction::Other);
 
-        if response.status().is_success() {
-            let command_response: CommandResponse = response.json().await?;
-            Ok(command_response)
-        } else {
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Failed to execute command: {:?}", response.status()),
-            )))
+        self.api.json(request).await
+    }
+
+    /// Runs a command on a started machine and waits for it to finish.
+    pub async fn exec(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+        request: ExecRequest,
+    ) -> Result<ExecOutput, FlyError> {
+        debug!(
+            "Executing command on machine {}: {:?}",
+            machine_id,
+            request.command()
+        );
+
+        let mut api_request = ApiRequest::post("/apps/{app}/machines/{machine}/exec")
+            .param("app", app_name)
+            .param("machine", machine_id)
+            .json(&request.body())
+            .action(RateLimitAction::Other);
+        if let Some(timeout) = request.timeout_value() {
+            // leave the API time to report that the command timed out
+            api_request = api_request.timeout(timeout + EXEC_TIMEOUT_MARGIN);
         }
+
+        let response: ExecResponse = self.api.json(api_request).await?;
+        request.output(response)
     }
 
     pub async fn get_machine(
         &self,
         app_name: &str,
         machine_id: &str,
-    ) -> Result<MachineResponse, Box<dyn Error>> {
+    ) -> Result<MachineResponse, FlyError> {
         debug!("Fetching details for machine {}", machine_id);
-        let url = format!("{}/apps/{}/machines/{}", API_BASE_URL, app_name, machine_id);
+        let request = ApiRequest::get("/apps/{app}/machines/{machine}")
+            .param("app", app_name)
+            .param("machine", machine_id)
+            .action(RateLimitAction::Read);
+
+        self.api.json(request).await
+    }
+
+    pub async fn get_lease(&self, app_name: &str, machine_id: &str) -> Result<Lease, FlyError> {
+        let request = ApiRequest::get("/apps/{app}/machines/{machine}/lease")
+            .param("app", app_name)
+            .param("machine", machine_id)
+            .action(RateLimitAction::Read);
 
-        let response = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.api_token)
-            .send()
+        let response: LeaseResponse = self.api.json(request).await?;
+        Ok(response.data)
+    }
+
+    /// Takes a lease on the machine for `ttl` seconds, failing with
+    /// [`FlyError::Conflict`] if someone else holds one.
+    pub async fn create_lease(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+        ttl: u64,
+        description: Option<&str>,
+    ) -> Result<Lease, FlyError> {
+        debug!("Acquiring lease on machine {}", machine_id);
+        let request = ApiRequest::post("/apps/{app}/machines/{machine}/lease")
+            .param("app", app_name)
+            .param("machine", machine_id)
+            .json(&LeaseRequest { ttl, description })
+            .action(RateLimitAction::Other);
+
+        let response: LeaseResponse = self.api.json(request).await?;
+        Ok(response.data)
+    }
+
+    /// Extends the lease identified by `nonce` to expire `ttl` seconds from now.
+    pub async fn refresh_lease(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+        nonce: &str,
+        ttl: u64,
+    ) -> Result<Lease, FlyError> {
+        let request = ApiRequest::post("/apps/{app}/machines/{machine}/lease")
+            .param("app", app_name)
+            .param("machine", machine_id)
+            .header(LEASE_NONCE_HEADER, nonce)
+            .json(&LeaseRequest {
+                ttl,
+                description: None,
+            })
+            .action(RateLimitAction::Other);
+
+        let response: LeaseResponse = self.api.json(request).await?;
+        Ok(response.data)
+    }
+
+    pub async fn release_lease(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+        nonce: &str,
+    ) -> Result<(), FlyError> {
+        debug!("Releasing lease on machine {}", machine_id);
+        let request = ApiRequest::delete("/apps/{app}/machines/{machine}/lease")
+            .param("app", app_name)
+            .param("machine", machine_id)
+            .header(LEASE_NONCE_HEADER, nonce)
+            .action(RateLimitAction::Other);
+
+        self.api.execute(request).await
+    }
+
+    /// Takes a lease on the machine and keeps it until the returned guard is dropped.
+    ///
+    /// ```rust,no_run
+    /// # use fly_sdk::machines::{MachineManager, MachineRequest};
+    /// # async fn deploy(machines: MachineManager, request: MachineRequest) -> Result<(), fly_sdk::FlyError> {
+    /// let lease = machines
+    ///     .acquire_lease("my-app", "3d8d9014b32d38", 30, Some("deploy"))
+    ///     .await?;
+    /// let machine = machines.get_machine("my-app", lease.machine_id()).await?;
+    /// machines
+    ///     .with_lease(&lease)
+    ///     .update_machine("my-app", lease.machine_id(), &machine.instance_id.unwrap(), request)
+    ///     .await?;
+    /// lease.release().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn acquire_lease(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+        ttl: u64,
+        description: Option<&str>,
+    ) -> Result<LeaseGuard, FlyError> {
+        let lease = self
+            .create_lease(app_name, machine_id, ttl, description)
             .await?;
+        Ok(LeaseGuard::new(
+            self.clone(),
+            app_name,
+            machine_id,
+            lease,
+            ttl,
+        ))
+    }
+
+    /// Takes the machine out of Fly proxy load balancing without stopping it.
+    pub async fn cordon(&self, app_name: &str, machine_id: &str) -> Result<(), FlyError> {
+        debug!("Cordoning machine {}", machine_id);
+        let request = self.leased(
+            ApiRequest::post("/apps/{app}/machines/{machine}/cordon")
+                .param("app", app_name)
+                .param("machine", machine_id)
+                .idempotent()
+                .action(RateLimitAction::Other),
+        );
+
+        self.api.execute(request).await
+    }
+
+    /// Puts a cordoned machine back into Fly proxy load balancing.
+    pub async fn uncordon(&self, app_name: &str, machine_id: &str) -> Result<(), FlyError> {
+        debug!("Uncordoning machine {}", machine_id);
+        let request = self.leased(
+            ApiRequest::post("/apps/{app}/machines/{machine}/uncordon")
+                .param("app", app_name)
+                .param("machine", machine_id)
+                .idempotent()
+                .action(RateLimitAction::Other),
+        );
+
+        self.api.execute(request).await
+    }
 
-        if response.status().is_success() {
-            let machine_response: MachineResponse = response.json().await?;
-            Ok(machine_response)
+    /// Cordons the machine, waits for in-flight work to settle, then stops it.
+    ///
+    /// Settling is only a CPU heuristic: the machine counts as settled once the CPU time
+    /// reported by [`MachineManager::list_processes`] stays the same across two polls. It
+    /// can't see open connections or queued work, so pick an `options.poll_interval`
+    /// longer than the gaps between requests. Returns whether the machine settled before
+    /// `options.timeout`; it is stopped either way. If its processes can't be listed, the
+    /// machine is uncordoned again and left running before the error is returned.
+    pub async fn drain(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+        options: DrainOptions,
+    ) -> Result<bool, FlyError> {
+        self.cordon(app_name, machine_id).await?;
+
+        let deadline = Instant::now() + options.timeout;
+        let mut last_usage = None;
+        let mut settled = false;
+        while Instant::now() < deadline {
+            let processes = match self.list_processes(app_name, machine_id).await {
+                Ok(processes) => processes,
+                Err(err) => {
+                    if let Err(uncordon_err) = self.uncordon(app_name, machine_id).await {
+                        warn!(
+                            "Failed to uncordon machine {} after a failed drain: {}",
+                            machine_id, uncordon_err
+                        );
+                    }
+                    return Err(err);
+                }
+            };
+            let usage = cpu_usage(&processes);
+            if last_usage == Some(usage) {
+                settled = true;
+                break;
+            }
+            last_usage = Some(usage);
+            tokio::time::sleep_until(deadline.min(Instant::now() + options.poll_interval)).await;
+        }
+        if settled {
+            debug!("Machine {} drained", machine_id);
         } else {
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Failed to get machine details: {:?}", response.status()),
-            )))
+            debug!(
+                "Machine {} still busy after {:?}, stopping it anyway",
+                machine_id, options.timeout
+            );
         }
+
+        self.stop_with(app_name, machine_id, options.stop).await?;
+        Ok(settled)
+    }
+
+    pub async fn get_metadata(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+    ) -> Result<HashMap<String, String>, FlyError> {
+        let request = ApiRequest::get("/apps/{app}/machines/{machine}/metadata")
+            .param("app", app_name)
+            .param("machine", machine_id)
+            .action(RateLimitAction::Read);
+
+        self.api.json(request).await
+    }
+
+    /// Sets a single metadata key without updating, and so restarting, the machine.
+    pub async fn set_metadata_key(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+        key: &str,
+        value: &str,
+    ) -> Result<(), FlyError> {
+        debug!("Setting metadata {} on machine {}", key, machine_id);
+        let request = self.leased(
+            ApiRequest::post("/apps/{app}/machines/{machine}/metadata/{key}")
+                .param("app", app_name)
+                .param("machine", machine_id)
+                .param("key", key)
+                .json(&serde_json::json!({ "value": value }))
+                .action(RateLimitAction::Update),
+        );
+
+        self.api.execute(request).await
+    }
+
+    pub async fn delete_metadata_key(
+        &self,
+        app_name: &str,
+        machine_id: &str,
+        key: &str,
+    ) -> Result<(), FlyError> {
+        debug!("Deleting metadata {} from machine {}", key, machine_id);
+        let request = self.leased(
+            ApiRequest::delete("/apps/{app}/machines/{machine}/metadata/{key}")
+                .param("app", app_name)
+                .param("machine", machine_id)
+                .param("key", key)
+                .action(RateLimitAction::Update),
+        );
+
+        self.api.execute(request).await
+    }
+}
+
+/// Whether a failed request may succeed if sent again later.
+fn is_transient(err: &FlyError) -> bool {
+    match err {
+        FlyError::Transport(err) => err.is_timeout() || err.is_connect(),
+        _ => err.status().is_some_and(|status| {
+            status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+        }),
     }
 }
+
+/// Regions this SDK doesn't know about yet are left for the API to fill in.
+pub(crate) fn parse_region(region: Option<String>) -> Option<MachineRegions> {
+    region.and_then(|region| serde_json::from_value(Value::String(region)).ok())
+}
@@ -0,0 +1,97 @@
+use crate::error::FlyError;
+use crate::machines::{MachineRegions, MachineResponse};
+
+/// How many machines [`MachineManager::create_many`](crate::machines::MachineManager::create_many)
+/// creates, and where.
+#[derive(Debug, Clone)]
+pub enum Replicas {
+    /// This many machines, in the template's region.
+    Count(usize),
+    /// One machine in each region, in order.
+    Regions(Vec<MachineRegions>),
+}
+
+impl Replicas {
+    pub(crate) fn regions(self) -> Vec<Option<MachineRegions>> {
+        match self {
+            Replicas::Count(count) => vec![None; count],
+            Replicas::Regions(regions) => regions.into_iter().map(Some).collect(),
+        }
+    }
+}
+
+impl From<usize> for Replicas {
+    fn from(count: usize) -> Self {
+        Replicas::Count(count)
+    }
+}
+
+impl From<Vec<MachineRegions>> for Replicas {
+    fn from(regions: Vec<MachineRegions>) -> Self {
+        Replicas::Regions(regions)
+    }
+}
+
+/// How [`MachineManager::create_many`](crate::machines::MachineManager::create_many) creates
+/// its machines.
+#[derive(Debug, Clone)]
+pub struct CreateManyOptions {
+    /// Most creations in flight at once. Each still waits for the create rate limit.
+    pub concurrency: usize,
+    /// Once more than this many creations fail, no new ones are started and the machines
+    /// created so far are destroyed. `None` keeps going regardless of failures.
+    pub rollback_after: Option<usize>,
+}
+
+impl Default for CreateManyOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 8,
+            rollback_after: None,
+        }
+    }
+}
+
+/// Outcome of [`MachineManager::create_many`](crate::machines::MachineManager::create_many).
+#[derive(Debug)]
+pub struct CreateManyReport {
+    /// One entry per requested machine, in the order they were requested, so index `i`
+    /// is the `i`-th replica. `None` marks a machine that was never attempted because
+    /// too many creations had already failed.
+    pub results: Vec<Option<Result<MachineResponse, FlyError>>>,
+    /// Whether too many creations failed and the created machines were destroyed.
+    pub rolled_back: bool,
+}
+
+impl CreateManyReport {
+    /// Machines that were created and, unless rolled back, still exist.
+    pub fn created(&self) -> impl Iterator<Item = &MachineResponse> {
+        self.results
+            .iter()
+            .flatten()
+            .filter_map(|result| result.as_ref().ok())
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &FlyError> {
+        self.results
+            .iter()
+            .flatten()
+            .filter_map(|result| result.as_ref().err())
+    }
+
+    /// Number of machines that were never attempted.
+    pub fn skipped(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|result| result.is_none())
+            .count()
+    }
+
+    pub fn all_created(&self) -> bool {
+        !self.rolled_back
+            && self
+                .results
+                .iter()
+                .all(|result| matches!(result, Some(Ok(_))))
+    }
+}
@@ -1,7 +1,7 @@
 use crate::machines::TimeoutConfig;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Checks {
     pub grace_period: Option<TimeoutConfig>,
     pub headers: Option<Vec<Header>>,
@@ -41,28 +41,34 @@ impl Checks {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl Default for Checks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum CheckKind {
     Informational,
     Readiness,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum CheckType {
     Tcp,
     Http,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum Protocol {
     Http,
     Https,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Header {
     pub name: String,
     pub values: Vec<String>,
@@ -83,6 +89,12 @@ pub struct CheckBuilder {
     check_type: Option<CheckType>,
 }
 
+impl Default for CheckBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CheckBuilder {
     pub fn new() -> Self {
         CheckBuilder {
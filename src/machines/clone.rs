@@ -0,0 +1,54 @@
+use crate::machines::{MachineConfig, MachineRegions};
+
+/// Edits applied to the source machine's config by
+/// [`MachineManager::clone_machine`](crate::machines::MachineManager::clone_machine).
+pub type ConfigOverrides = Box<dyn FnOnce(&mut MachineConfig) + Send>;
+
+/// How [`MachineManager::clone_machine`](crate::machines::MachineManager::clone_machine)
+/// derives the new machine from the source.
+///
+/// ```rust
+/// use fly_sdk::machines::{CloneOptions, MachineRegions};
+///
+/// let options = CloneOptions {
+///     region: Some(MachineRegions::Fra),
+///     attach_new_volume: true,
+///     ..CloneOptions::default()
+/// }
+/// .with_config_overrides(|config| {
+///     config.env.get_or_insert_with(Default::default).insert("ROLE".into(), "replica".into());
+/// });
+/// ```
+#[derive(Default)]
+pub struct CloneOptions {
+    /// Region of the new machine; defaults to the source's region.
+    pub region: Option<MachineRegions>,
+    /// Name of the new machine; Fly picks one by default.
+    pub name: Option<String>,
+    pub config_overrides: Option<ConfigOverrides>,
+    /// Creates a fresh, empty volume for each of the source's mounts, sized like the
+    /// source volume. Required when the source has mounts, since a volume can only be
+    /// attached to one machine.
+    pub attach_new_volume: bool,
+}
+
+impl CloneOptions {
+    pub fn with_config_overrides(
+        mut self,
+        overrides: impl FnOnce(&mut MachineConfig) + Send + 'static,
+    ) -> Self {
+        self.config_overrides = Some(Box::new(overrides));
+        self
+    }
+}
+
+impl std::fmt::Debug for CloneOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CloneOptions")
+            .field("region", &self.region)
+            .field("name", &self.name)
+            .field("config_overrides", &self.config_overrides.is_some())
+            .field("attach_new_volume", &self.attach_new_volume)
+            .finish()
+    }
+}
@@ -0,0 +1,393 @@
+use crate::error::FlyError;
+use crate::machines::api_manager::parse_region;
+use crate::machines::{
+    ListMachinesQuery, MachineConfig, MachineLifecycleState, MachineManager, MachineRequest,
+    MachineResponse, MachineState,
+};
+use futures_util::future::join_all;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+/// Progress reported by [`MachineManager::deploy`](crate::machines::MachineManager::deploy).
+/// Batches are numbered from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeployEvent {
+    Started {
+        machines: usize,
+        batches: usize,
+    },
+    BatchStarted {
+        batch: usize,
+        machine_ids: Vec<String>,
+    },
+    MachineUpdated {
+        machine_id: String,
+        instance_id: Option<String>,
+    },
+    /// The machine is started and all its checks pass, or it was stopped to begin with.
+    MachineReady {
+        machine_id: String,
+    },
+    BatchFailed {
+        batch: usize,
+        machine_id: String,
+        error: String,
+    },
+    RollingBack {
+        machine_ids: Vec<String>,
+    },
+    MachineRolledBack {
+        machine_id: String,
+    },
+    RollbackFailed {
+        machine_id: String,
+        error: String,
+    },
+    Finished {
+        updated: usize,
+    },
+}
+
+type ProgressCallback = Arc<dyn Fn(&DeployEvent) + Send + Sync>;
+
+/// Updates an app's machines a few at a time, as used by
+/// [`MachineManager::deploy`](crate::machines::MachineManager::deploy).
+///
+/// ```rust
+/// use fly_sdk::machines::deploy::RollingDeploy;
+/// use std::time::Duration;
+///
+/// let strategy = RollingDeploy::new()
+///     .max_unavailable(2)
+///     .health_timeout(Duration::from_secs(120))
+///     .on_progress(|event| println!("{:?}", event));
+/// ```
+#[derive(Clone)]
+pub struct RollingDeploy {
+    max_unavailable: usize,
+    health_timeout: Duration,
+    machines: ListMachinesQuery,
+    on_progress: Option<ProgressCallback>,
+}
+
+impl Default for RollingDeploy {
+    fn default() -> Self {
+        Self {
+            max_unavailable: 1,
+            health_timeout: Duration::from_secs(300),
+            machines: ListMachinesQuery::new(),
+            on_progress: None,
+        }
+    }
+}
+
+impl RollingDeploy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Machines updated at once, one batch at a time; 1 by default.
+    pub fn max_unavailable(mut self, max_unavailable: usize) -> Self {
+        self.max_unavailable = max_unavailable;
+        self
+    }
+
+    /// How long an updated machine may take to start and have all its checks pass;
+    /// 5 minutes by default.
+    pub fn health_timeout(mut self, health_timeout: Duration) -> Self {
+        self.health_timeout = health_timeout;
+        self
+    }
+
+    /// Only deploys to the machines `query` lists, e.g. one process group.
+    pub fn machines(mut self, query: ListMachinesQuery) -> Self {
+        self.machines = query;
+        self
+    }
+
+    pub fn on_progress(
+        mut self,
+        on_progress: impl Fn(&DeployEvent) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_progress = Some(Arc::new(on_progress));
+        self
+    }
+
+    fn emit(&self, event: DeployEvent) {
+        debug!("Deploy progress: {:?}", event);
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(&event);
+        }
+    }
+}
+
+impl std::fmt::Debug for RollingDeploy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RollingDeploy")
+            .field("max_unavailable", &self.max_unavailable)
+            .field("health_timeout", &self.health_timeout)
+            .field("machines", &self.machines)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Outcome of a deploy where every batch came up healthy.
+#[derive(Debug, Clone)]
+pub struct DeployReport {
+    /// Ids of the updated machines, in the order they were updated.
+    pub machine_ids: Vec<String>,
+}
+
+/// A machine as it was before the deploy touched it.
+struct Previous {
+    machine_id: String,
+    config: MachineConfig,
+    name: Option<String>,
+    region: Option<String>,
+    running: bool,
+}
+
+pub(crate) async fn rolling(
+    manager: &MachineManager,
+    app_name: &str,
+    update: &(dyn Fn(&mut MachineConfig) + Send + Sync),
+    strategy: &RollingDeploy,
+) -> Result<DeployReport, FlyError> {
+    if strategy.max_unavailable == 0 {
+        return Err(FlyError::Config(
+            "a rolling deploy needs max_unavailable of at least 1".to_string(),
+        ));
+    }
+    let mut targets = manager
+        .list_with(app_name, strategy.machines.clone())
+        .await?;
+    targets.retain(|machine| {
+        !matches!(
+            machine.state,
+            Some(MachineLifecycleState::Destroying | MachineLifecycleState::Destroyed)
+        )
+    });
+    targets.sort_by(|a, b| a.id.cmp(&b.id));
+    let batches: Vec<&[MachineResponse]> = targets.chunks(strategy.max_unavailable).collect();
+    strategy.emit(DeployEvent::Started {
+        machines: targets.len(),
+        batches: batches.len(),
+    });
+
+    let mut updated: Vec<Previous> = Vec::new();
+    for (index, batch) in batches.into_iter().enumerate() {
+        let batch_number = index + 1;
+        strategy.emit(DeployEvent::BatchStarted {
+            batch: batch_number,
+            machine_ids: batch
+                .iter()
+                .filter_map(|machine| machine.id.clone())
+                .collect(),
+        });
+
+        let outcomes = join_all(
+            batch
+                .iter()
+                .map(|machine| update_one(manager, app_name, machine, update, strategy)),
+        )
+        .await;
+        let mut failure = None;
+        for (previous, result) in outcomes {
+            let machine_id = previous
+                .as_ref()
+                .map(|previous| previous.machine_id.clone());
+            updated.extend(previous);
+            if let Err((machine_id_on_error, err)) = result {
+                failure.get_or_insert((machine_id.unwrap_or(machine_id_on_error), err));
+            }
+        }
+
+        if let Some((machine_id, err)) = failure {
+            strategy.emit(DeployEvent::BatchFailed {
+                batch: batch_number,
+                machine_id,
+                error: err.to_string(),
+            });
+            roll_back(manager, app_name, &updated, strategy).await;
+            return Err(err);
+        }
+    }
+
+    strategy.emit(DeployEvent::Finished {
+        updated: updated.len(),
+    });
+    Ok(DeployReport {
+        machine_ids: updated
+            .into_iter()
+            .map(|previous| previous.machine_id)
+            .collect(),
+    })
+}
+
+/// Updates one machine and waits for it to be ready. Returns the machine's previous
+/// state once the update was accepted, even when the machine then failed to start or
+/// failed its checks, so it can be rolled back.
+async fn update_one(
+    manager: &MachineManager,
+    app_name: &str,
+    machine: &MachineResponse,
+    update: &(dyn Fn(&mut MachineConfig) + Send + Sync),
+    strategy: &RollingDeploy,
+) -> (Option<Previous>, Result<(), (String, FlyError)>) {
+    let machine_id = machine.id.clone().unwrap_or_default();
+    let Some(config) = machine.config.clone() else {
+        let err = FlyError::Config(format!("machine {} has no config to deploy", machine_id));
+        return (None, Err((machine_id, err)));
+    };
+    let previous = Previous {
+        machine_id: machine_id.clone(),
+        config: config.clone(),
+        name: machine.name.clone(),
+        region: machine.region.clone(),
+        running: machine
+            .state
+            .as_ref()
+            .is_some_and(|state| state.is_running() || *state == MachineLifecycleState::Starting),
+    };
+
+    let mut new_config = config;
+    update(&mut new_config);
+    let request = previous.request(new_config);
+    let instance_id = machine.instance_id.clone().unwrap_or_default();
+    let new_machine = match manager
+        .send_update(app_name, &machine_id, &instance_id, request)
+        .await
+    {
+        Ok(new_machine) => new_machine,
+        Err(err) => return (None, Err((machine_id, err))),
+    };
+    strategy.emit(DeployEvent::MachineUpdated {
+        machine_id: machine_id.clone(),
+        instance_id: new_machine.instance_id.clone(),
+    });
+
+    if let Err(err) = wait_until_ready(manager, app_name, &new_machine, &previous, strategy).await {
+        return (Some(previous), Err((machine_id, err)));
+    }
+    strategy.emit(DeployEvent::MachineReady {
+        machine_id: machine_id.clone(),
+    });
+    (Some(previous), Ok(()))
+}
+
+impl Previous {
+    fn request(&self, config: MachineConfig) -> MachineRequest {
+        MachineRequest::new(config, self.name.clone(), parse_region(self.region.clone()))
+            .with_skip_launch(!self.running)
+    }
+}
+
+/// Waits for the new instance to be started with all its checks passing, or stopped if
+/// the machine was stopped before, all within the strategy's health timeout.
+async fn wait_until_ready(
+    manager: &MachineManager,
+    app_name: &str,
+    new_machine: &MachineResponse,
+    previous: &Previous,
+    strategy: &RollingDeploy,
+) -> Result<(), FlyError> {
+    let deadline = Instant::now() + strategy.health_timeout;
+    let desired_state = if previous.running {
+        MachineState::Started
+    } else {
+        MachineState::Stopped
+    };
+    manager
+        .wait_for_states(
+            app_name,
+            &previous.machine_id,
+            &[desired_state],
+            strategy.health_timeout,
+            new_machine.instance_id.as_deref(),
+        )
+        .await?;
+    if !previous.running {
+        return Ok(());
+    }
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    wait_for_checks(manager, app_name, &previous.machine_id, remaining).await
+}
+
+/// Polls the machine until all of its checks pass.
+async fn wait_for_checks(
+    manager: &MachineManager,
+    app_name: &str,
+    machine_id: &str,
+    timeout: Duration,
+) -> Result<(), FlyError> {
+    let deadline = Instant::now() + timeout;
+    let mut poll_interval = Duration::from_millis(200);
+    loop {
+        let machine = manager.get_machine(app_name, machine_id).await?;
+        let checks = machine.checks.unwrap_or_default();
+        let failing = checks
+            .into_iter()
+            .find(|check| check.status.as_deref() != Some("passing"));
+        let Some(failing) = failing else {
+            return Ok(());
+        };
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(FlyError::HealthCheckFailed {
+                machine_id: machine_id.to_string(),
+                check: failing.name,
+                output: failing.output,
+            });
+        }
+        tokio::time::sleep(poll_interval.min(remaining)).await;
+        poll_interval = (poll_interval * 2).min(Duration::from_secs(2));
+    }
+}
+
+async fn roll_back(
+    manager: &MachineManager,
+    app_name: &str,
+    updated: &[Previous],
+    strategy: &RollingDeploy,
+) {
+    strategy.emit(DeployEvent::RollingBack {
+        machine_ids: updated
+            .iter()
+            .map(|previous| previous.machine_id.clone())
+            .collect(),
+    });
+    for previous in updated {
+        let result = async {
+            let current = manager.get_machine(app_name, &previous.machine_id).await?;
+            let instance_id = current.instance_id.unwrap_or_default();
+            manager
+                .update_machine(
+                    app_name,
+                    &previous.machine_id,
+                    &instance_id,
+                    previous.request(previous.config.clone()),
+                )
+                .await
+        }
+        .await;
+
+        match result {
+            Ok(_) => strategy.emit(DeployEvent::MachineRolledBack {
+                machine_id: previous.machine_id.clone(),
+            }),
+            Err(err) => {
+                warn!(
+                    "Failed to roll back machine {}: {}",
+                    previous.machine_id, err
+                );
+                strategy.emit(DeployEvent::RollbackFailed {
+                    machine_id: previous.machine_id.clone(),
+                    error: err.to_string(),
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use crate::machines::{ProcessResponse, StopOptions};
+use std::time::Duration;
+
+/// How [`MachineManager::drain`](crate::machines::MachineManager::drain) waits for a
+/// cordoned machine to go idle before stopping it.
+#[derive(Debug, Clone)]
+pub struct DrainOptions {
+    /// Longest time to wait for the machine to settle before stopping it anyway.
+    pub timeout: Duration,
+    /// Delay between two samples of the machine's processes.
+    pub poll_interval: Duration,
+    /// How the machine is stopped once drained.
+    pub stop: StopOptions,
+}
+
+impl Default for DrainOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            poll_interval: Duration::from_secs(1),
+            stop: StopOptions::default(),
+        }
+    }
+}
+
+/// Total CPU time of the machine's processes, which stops growing once they go idle.
+pub(crate) fn cpu_usage(processes: &[ProcessResponse]) -> u64 {
+    processes
+        .iter()
+        .map(|process| u64::from(process.cpu.unwrap_or_default()))
+        .sum()
+}
@@ -1,5 +1,6 @@
 use crate::machines::{
     MachineConfig,
+    MachineLifecycleState,
     MachineRegions,
     // Checks, DnsConfig, FileConfig, GuestConfig, Header, InitConfig,
     // MetricsConfig, MountConfig, ProcessConfig, RestartPolicy, ServiceConfig, StaticConfig,
@@ -8,7 +9,7 @@ use crate::machines::{
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MachineRequest {
     pub name: Option<String>,
     pub config: MachineConfig,
@@ -17,6 +18,10 @@ pub struct MachineRequest {
     pub lsvd: Option<bool>,
     pub skip_launch: Option<bool>,
     pub skip_service_registration: Option<bool>,
+    /// Instance the update applies to; set by
+    /// [`MachineManager::update_machine`](crate::machines::MachineManager::update_machine).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_version: Option<String>,
 }
 
 impl MachineRequest {
@@ -26,13 +31,14 @@ impl MachineRequest {
         region: Option<MachineRegions>,
     ) -> Self {
         Self {
-            name: name,
+            name,
             config,
-            region: region,
+            region,
             lease_ttl: None,
             lsvd: None,
             skip_launch: None,
             skip_service_registration: None,
+            current_version: None,
         }
     }
 
@@ -61,7 +67,7 @@ impl MachineRequest {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MachineResponse {
     pub id: Option<String>,
     pub checks: Option<Vec<CheckResponse>>,
@@ -76,21 +82,36 @@ pub struct MachineResponse {
     pub nonce: Option<String>,
     pub private_ip: Option<String>,
     pub region: Option<String>,
-    pub state: Option<String>,
+    pub state: Option<MachineLifecycleState>,
     pub updated_at: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StartResponse {
+    pub previous_state: Option<MachineLifecycleState>,
+    pub migrated: Option<bool>,
+    pub new_host: Option<String>,
+}
+
+impl StartResponse {
+    /// Whether the machine was resumed from a snapshot rather than booted from scratch.
+    pub fn resumed_from_snapshot(&self) -> bool {
+        self.previous_state == Some(MachineLifecycleState::Suspended)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EventResponse {
     pub id: Option<String>,
     pub request: Option<Value>,
     pub source: Option<String>,
     pub status: Option<String>,
     pub timestamp: Option<u64>,
+    #[serde(rename = "type")]
     pub event_type: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum HostStatusEnum {
     Ok,
@@ -98,7 +119,7 @@ pub enum HostStatusEnum {
     Unreachable,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ImageRef {
     pub digest: Option<String>,
     pub labels: Option<Value>,
@@ -107,15 +128,10 @@ pub struct ImageRef {
     pub tag: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CheckResponse {
     pub name: Option<String>,
     pub output: Option<String>,
     pub status: Option<String>,
     pub updated_at: Option<String>,
 }
-
-#[derive(Serialize, Deserialize, Debug)]
-struct CreateMachinesResponse {
-    machines: Vec<MachineResponse>,
-}
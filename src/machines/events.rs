@@ -0,0 +1,186 @@
+use crate::error::FlyError;
+use crate::machines::{EventResponse, MachineManager};
+use futures_util::stream::{self, Stream};
+use serde_json::Value;
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+/// A machine event, as yielded by
+/// [`MachineManager::watch_events`](crate::machines::MachineManager::watch_events).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MachineEvent {
+    pub id: String,
+    /// Unix timestamp, in milliseconds.
+    pub timestamp: Option<u64>,
+    /// Who caused the event, `user` or `flyd`.
+    pub source: Option<String>,
+    /// State the machine moved to, such as `started` or `stopped`.
+    pub status: Option<String>,
+    pub kind: MachineEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MachineEventKind {
+    Launch,
+    Start,
+    /// The main process exited; `requested_stop` is false when it crashed or was OOM
+    /// killed rather than stopped through the API.
+    Exit {
+        exit_code: Option<i64>,
+        oom_killed: bool,
+        requested_stop: bool,
+    },
+    Update,
+    Restart,
+    Destroy,
+    /// Any other event type, such as `stop` or `suspend`.
+    Other(String),
+}
+
+impl From<EventResponse> for MachineEvent {
+    fn from(event: EventResponse) -> Self {
+        let event_type = event.event_type.unwrap_or_default();
+        let kind = match event_type.as_str() {
+            "launch" => MachineEventKind::Launch,
+            "start" => MachineEventKind::Start,
+            "exit" => {
+                let exit = event
+                    .request
+                    .as_ref()
+                    .and_then(|request| request.get("exit_event"));
+                let flag = |name: &str| {
+                    exit.and_then(|exit| exit.get(name))
+                        .and_then(Value::as_bool)
+                        .unwrap_or(false)
+                };
+                MachineEventKind::Exit {
+                    exit_code: exit
+                        .and_then(|exit| exit.get("exit_code"))
+                        .and_then(Value::as_i64),
+                    oom_killed: flag("oom_killed"),
+                    requested_stop: flag("requested_stop"),
+                }
+            }
+            "update" => MachineEventKind::Update,
+            "restart" => MachineEventKind::Restart,
+            "destroy" => MachineEventKind::Destroy,
+            _ => MachineEventKind::Other(event_type.clone()),
+        };
+        // events have always carried an id, but don't count on it for de-duplication
+        let id = event.id.unwrap_or_else(|| {
+            format!(
+                "{}-{}-{}",
+                event.timestamp.unwrap_or_default(),
+                event_type,
+                event.status.as_deref().unwrap_or_default()
+            )
+        });
+
+        Self {
+            id,
+            timestamp: event.timestamp,
+            source: event.source,
+            status: event.status,
+            kind,
+        }
+    }
+}
+
+/// How [`MachineManager::watch_events_with`](crate::machines::MachineManager::watch_events_with)
+/// polls for events.
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// Delay between two listings of the machine's events.
+    pub poll_interval: Duration,
+    /// Whether to yield the events the machine already had when watching started.
+    pub include_existing: bool,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(2),
+            include_existing: false,
+        }
+    }
+}
+
+struct Watch {
+    machines: MachineManager,
+    app_name: String,
+    machine_id: String,
+    options: WatchOptions,
+    seen: HashSet<String>,
+    pending: VecDeque<MachineEvent>,
+    polled: bool,
+    done: bool,
+}
+
+impl Watch {
+    async fn poll(&mut self) -> Result<(), FlyError> {
+        if self.polled {
+            tokio::time::sleep(self.options.poll_interval).await;
+        }
+        let events = self
+            .machines
+            .list_events(&self.app_name, &self.machine_id)
+            .await?;
+        let yield_new = self.polled || self.options.include_existing;
+        self.polled = true;
+
+        // the API lists events newest first and only keeps the latest ones, so remembering
+        // the ids of the last listing is enough to spot new events
+        let events: Vec<MachineEvent> = events.into_iter().map(MachineEvent::from).collect();
+        let seen: HashSet<String> = events.iter().map(|event| event.id.clone()).collect();
+        if yield_new {
+            self.pending.extend(
+                events
+                    .into_iter()
+                    .rev()
+                    .filter(|event| !self.seen.contains(&event.id)),
+            );
+        }
+        self.seen = seen;
+        Ok(())
+    }
+}
+
+pub(crate) async fn watch(
+    machines: MachineManager,
+    app_name: &str,
+    machine_id: &str,
+    options: WatchOptions,
+) -> Result<impl Stream<Item = Result<MachineEvent, FlyError>> + Send + 'static, FlyError> {
+    let mut watch = Watch {
+        machines,
+        app_name: app_name.to_string(),
+        machine_id: machine_id.to_string(),
+        options,
+        seen: HashSet::new(),
+        pending: VecDeque::new(),
+        polled: false,
+        done: false,
+    };
+    // list once up front so events from here on count as new, even before the first poll
+    watch.poll().await?;
+
+    Ok(stream::unfold(watch, |mut watch| async move {
+        loop {
+            if let Some(event) = watch.pending.pop_front() {
+                if event.kind == MachineEventKind::Destroy {
+                    // nothing happens to a machine after it's destroyed
+                    watch.pending.clear();
+                    watch.done = true;
+                }
+                return Some((Ok(event), watch));
+            }
+            if watch.done {
+                return None;
+            }
+            if let Err(err) = watch.poll().await {
+                watch.done = err.is_not_found();
+                return Some((Err(err), watch));
+            }
+        }
+    }))
+}
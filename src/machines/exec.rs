@@ -0,0 +1,167 @@
+use crate::error::FlyError;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::time::Duration;
+
+/// A command to run on a machine with
+/// [`MachineManager::exec`](crate::machines::MachineManager::exec).
+///
+/// ```rust
+/// use fly_sdk::machines::ExecRequest;
+/// use std::time::Duration;
+///
+/// let request = ExecRequest::new(["psql", "-f", "-"])
+///     .stdin("select 1;")
+///     .timeout(Duration::from_secs(300));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ExecRequest {
+    command: Vec<String>,
+    stdin: Option<String>,
+    timeout: Option<Duration>,
+    container: Option<String>,
+    base64_stdout: bool,
+}
+
+impl ExecRequest {
+    pub fn new<I, S>(command: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            command: command.into_iter().map(Into::into).collect(),
+            stdin: None,
+            timeout: None,
+            container: None,
+            base64_stdout: false,
+        }
+    }
+
+    /// Written to the command's standard input.
+    pub fn stdin(mut self, stdin: impl Into<String>) -> Self {
+        self.stdin = Some(stdin.into());
+        self
+    }
+
+    /// How long the command may run; the API rounds it up to whole seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Runs the command in this container of the machine instead of the main one.
+    pub fn container(mut self, container: &str) -> Self {
+        self.container = Some(container.to_string());
+        self
+    }
+
+    /// Decodes the command's output from base64, for commands that pipe binary output
+    /// through `base64` since the API only returns text.
+    pub fn base64_stdout(mut self) -> Self {
+        self.base64_stdout = true;
+        self
+    }
+
+    pub(crate) fn command(&self) -> &[String] {
+        &self.command
+    }
+
+    pub(crate) fn timeout_value(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub(crate) fn body(&self) -> ExecBody<'_> {
+        ExecBody {
+            command: &self.command,
+            stdin: self.stdin.as_deref(),
+            timeout: self
+                .timeout
+                .map(|timeout| timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0)),
+            container: self.container.as_deref(),
+        }
+    }
+
+    pub(crate) fn output(&self, response: ExecResponse) -> Result<ExecOutput, FlyError> {
+        let stdout = response.stdout.unwrap_or_default();
+        let stdout = if self.base64_stdout {
+            base64::engine::general_purpose::STANDARD
+                .decode(stdout.trim())
+                .map_err(|err| {
+                    let source = serde::de::Error::custom(format!("stdout is not base64: {}", err));
+                    FlyError::decode(source, &stdout, None)
+                })?
+        } else {
+            stdout.into_bytes()
+        };
+
+        Ok(ExecOutput {
+            exit_code: response.exit_code,
+            // the API reports 0 when the command wasn't killed by a signal
+            exit_signal: response.exit_signal.filter(|signal| *signal != 0),
+            stdout,
+            stderr: response.stderr.unwrap_or_default().into_bytes(),
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct ExecBody<'a> {
+    command: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stdin: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeout: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    container: Option<&'a str>,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct ExecResponse {
+    exit_code: Option<i32>,
+    exit_signal: Option<i32>,
+    stdout: Option<String>,
+    stderr: Option<String>,
+}
+
+/// Result of [`MachineManager::exec`](crate::machines::MachineManager::exec).
+///
+/// A command that ran but failed is still `Ok`; call [`ExecOutput::check`] to treat a
+/// non-zero exit code or a signal as an error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecOutput {
+    /// `None` if the API didn't report one, which [`ExecOutput::success`] treats as failure.
+    pub exit_code: Option<i32>,
+    /// Signal that killed the command, if any.
+    pub exit_signal: Option<i32>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl ExecOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0) && self.exit_signal.is_none()
+    }
+
+    pub fn stdout_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stdout)
+    }
+
+    pub fn stderr_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stderr)
+    }
+
+    /// Returns the output if the command succeeded, or [`FlyError::CommandFailed`]
+    /// carrying its stderr.
+    pub fn check(self) -> Result<Self, FlyError> {
+        if self.success() {
+            return Ok(self);
+        }
+        Err(FlyError::CommandFailed {
+            exit_code: self.exit_code,
+            exit_signal: self.exit_signal,
+            stderr: self.stderr_lossy().into_owned(),
+        })
+    }
+}
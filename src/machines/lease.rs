@@ -0,0 +1,171 @@
+use crate::machines::MachineManager;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Header carrying the nonce of the lease held on a machine.
+pub const LEASE_NONCE_HEADER: &str = "fly-machine-lease-nonce";
+
+/// An exclusive lease on a machine.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Lease {
+    pub nonce: String,
+    /// Unix timestamp, in seconds, at which the lease expires unless refreshed.
+    pub expires_at: u64,
+    pub owner: Option<String>,
+    pub description: Option<String>,
+    pub version: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct LeaseRequest<'a> {
+    pub ttl: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<&'a str>,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct LeaseResponse {
+    pub data: Lease,
+}
+
+/// A lease acquired with [`MachineManager::acquire_lease`].
+///
+/// The lease is refreshed in the background at half its TTL and released when the guard
+/// is dropped. Pass the guard to [`MachineManager::with_lease`] so mutating calls carry
+/// its nonce; calls from other holders fail with [`FlyError::Conflict`](crate::FlyError::Conflict)
+/// while the lease is held.
+///
+/// Dropping the guard releases the lease in a background task, which requires a Tokio
+/// runtime; use [`LeaseGuard::release`] to wait for the release instead.
+pub struct LeaseGuard {
+    machines: MachineManager,
+    app_name: String,
+    machine_id: String,
+    lease: Arc<Mutex<Lease>>,
+    refresher: Option<JoinHandle<()>>,
+}
+
+impl LeaseGuard {
+    pub(crate) fn new(
+        machines: MachineManager,
+        app_name: &str,
+        machine_id: &str,
+        lease: Lease,
+        ttl: u64,
+    ) -> Self {
+        let lease = Arc::new(Mutex::new(lease));
+        let refresher = tokio::spawn(refresh_loop(
+            machines.clone(),
+            app_name.to_string(),
+            machine_id.to_string(),
+            lease.clone(),
+            ttl,
+        ));
+
+        Self {
+            machines,
+            app_name: app_name.to_string(),
+            machine_id: machine_id.to_string(),
+            lease,
+            refresher: Some(refresher),
+        }
+    }
+
+    pub fn app_name(&self) -> &str {
+        &self.app_name
+    }
+
+    pub fn machine_id(&self) -> &str {
+        &self.machine_id
+    }
+
+    pub fn nonce(&self) -> String {
+        self.lease().nonce
+    }
+
+    /// The lease as of its last refresh.
+    pub fn lease(&self) -> Lease {
+        self.lease
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /// Stops refreshing and releases the lease.
+    pub async fn release(mut self) -> Result<(), crate::FlyError> {
+        if let Some(refresher) = self.refresher.take() {
+            refresher.abort();
+        }
+        self.machines
+            .release_lease(&self.app_name, &self.machine_id, &self.nonce())
+            .await
+    }
+}
+
+impl std::fmt::Debug for LeaseGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LeaseGuard")
+            .field("app_name", &self.app_name)
+            .field("machine_id", &self.machine_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for LeaseGuard {
+    fn drop(&mut self) {
+        let Some(refresher) = self.refresher.take() else {
+            return;
+        };
+        refresher.abort();
+
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            warn!(
+                "Lease on machine {} dropped outside a Tokio runtime, leaving it to expire",
+                self.machine_id
+            );
+            return;
+        };
+        let (machines, app_name, machine_id, nonce) = (
+            self.machines.clone(),
+            self.app_name.clone(),
+            self.machine_id.clone(),
+            self.nonce(),
+        );
+        runtime.spawn(async move {
+            if let Err(err) = machines.release_lease(&app_name, &machine_id, &nonce).await {
+                warn!("Failed to release lease on machine {}: {}", machine_id, err);
+            }
+        });
+    }
+}
+
+async fn refresh_loop(
+    machines: MachineManager,
+    app_name: String,
+    machine_id: String,
+    lease: Arc<Mutex<Lease>>,
+    ttl: u64,
+) {
+    let interval = Duration::from_secs(ttl.max(2) / 2);
+    loop {
+        tokio::time::sleep(interval).await;
+        let nonce = lease
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .nonce
+            .clone();
+        match machines
+            .refresh_lease(&app_name, &machine_id, &nonce, ttl)
+            .await
+        {
+            Ok(refreshed) => {
+                debug!("Refreshed lease on machine {}", machine_id);
+                *lease.lock().unwrap_or_else(|err| err.into_inner()) = refreshed;
+            }
+            Err(err) => warn!("Failed to refresh lease on machine {}: {}", machine_id, err),
+        }
+    }
+}
@@ -6,13 +6,22 @@ use crate::machines::{
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum MachineState {
     Started,
     Stopped,
     Suspended,
     Destroyed,
+    /// Only observable by polling; the API's wait endpoint can't wait for it.
+    Failed,
+}
+
+impl MachineState {
+    /// Whether the API's wait endpoint accepts this state.
+    pub(crate) fn is_waitable(&self) -> bool {
+        !matches!(self, MachineState::Failed)
+    }
 }
 
 impl std::fmt::Display for MachineState {
@@ -22,12 +31,131 @@ impl std::fmt::Display for MachineState {
             MachineState::Stopped => "stopped",
             MachineState::Suspended => "suspended",
             MachineState::Destroyed => "destroyed",
+            MachineState::Failed => "failed",
         };
         write!(f, "{}", state_str)
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Every state a machine reports in [`MachineResponse::state`](crate::machines::MachineResponse::state).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum MachineLifecycleState {
+    Created,
+    Starting,
+    Started,
+    Stopping,
+    Stopped,
+    Suspending,
+    Suspended,
+    Updating,
+    Replacing,
+    Destroying,
+    Destroyed,
+    Failed,
+    /// A state this version of the SDK doesn't know about.
+    Unknown(String),
+}
+
+impl MachineLifecycleState {
+    pub fn as_str(&self) -> &str {
+        match self {
+            MachineLifecycleState::Created => "created",
+            MachineLifecycleState::Starting => "starting",
+            MachineLifecycleState::Started => "started",
+            MachineLifecycleState::Stopping => "stopping",
+            MachineLifecycleState::Stopped => "stopped",
+            MachineLifecycleState::Suspending => "suspending",
+            MachineLifecycleState::Suspended => "suspended",
+            MachineLifecycleState::Updating => "updating",
+            MachineLifecycleState::Replacing => "replacing",
+            MachineLifecycleState::Destroying => "destroying",
+            MachineLifecycleState::Destroyed => "destroyed",
+            MachineLifecycleState::Failed => "failed",
+            MachineLifecycleState::Unknown(state) => state,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self, MachineLifecycleState::Started)
+    }
+
+    /// Whether the machine is on its way to another state and will settle on its own.
+    pub fn is_transitioning(&self) -> bool {
+        matches!(
+            self,
+            MachineLifecycleState::Starting
+                | MachineLifecycleState::Stopping
+                | MachineLifecycleState::Suspending
+                | MachineLifecycleState::Updating
+                | MachineLifecycleState::Replacing
+                | MachineLifecycleState::Destroying
+        )
+    }
+
+    /// Whether the machine is gone or broken, and has to be replaced rather than started.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            MachineLifecycleState::Destroyed | MachineLifecycleState::Failed
+        )
+    }
+}
+
+impl From<String> for MachineLifecycleState {
+    fn from(state: String) -> Self {
+        match state.as_str() {
+            "created" => MachineLifecycleState::Created,
+            "starting" => MachineLifecycleState::Starting,
+            "started" => MachineLifecycleState::Started,
+            "stopping" => MachineLifecycleState::Stopping,
+            "stopped" => MachineLifecycleState::Stopped,
+            "suspending" => MachineLifecycleState::Suspending,
+            "suspended" => MachineLifecycleState::Suspended,
+            "updating" => MachineLifecycleState::Updating,
+            "replacing" => MachineLifecycleState::Replacing,
+            "destroying" => MachineLifecycleState::Destroying,
+            "destroyed" => MachineLifecycleState::Destroyed,
+            "failed" => MachineLifecycleState::Failed,
+            _ => MachineLifecycleState::Unknown(state),
+        }
+    }
+}
+
+impl From<MachineLifecycleState> for String {
+    fn from(state: MachineLifecycleState) -> Self {
+        match state {
+            MachineLifecycleState::Unknown(state) => state,
+            known => known.as_str().to_string(),
+        }
+    }
+}
+
+impl From<MachineState> for MachineLifecycleState {
+    fn from(state: MachineState) -> Self {
+        match state {
+            MachineState::Started => MachineLifecycleState::Started,
+            MachineState::Stopped => MachineLifecycleState::Stopped,
+            MachineState::Suspended => MachineLifecycleState::Suspended,
+            MachineState::Destroyed => MachineLifecycleState::Destroyed,
+            MachineState::Failed => MachineLifecycleState::Failed,
+        }
+    }
+}
+
+impl PartialEq<MachineState> for MachineLifecycleState {
+    fn eq(&self, other: &MachineState) -> bool {
+        self.as_str() == other.to_string()
+    }
+}
+
+impl std::fmt::Display for MachineLifecycleState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MachineConfig {
     pub image: String,
     pub guest: Option<GuestConfig>,
@@ -79,6 +207,7 @@ impl MachineConfig {
         MachineConfigBuilder::new()
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         image: String,
         auto_destroy: Option<bool>,
@@ -125,6 +254,12 @@ pub struct MachineConfigBuilder {
     config: MachineConfig,
 }
 
+impl Default for MachineConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MachineConfigBuilder {
     pub fn new() -> Self {
         Self {
@@ -155,7 +290,7 @@ impl MachineConfigBuilder {
         gpu_bid_price: Option<f64>,
     ) -> Self {
         self.config.restart = Some(RestartPolicy {
-            policy: policy,
+            policy,
             max_retries,
             gpu_bid_price,
         });
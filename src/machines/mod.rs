@@ -1,30 +1,50 @@
 pub mod api_manager;
+pub mod bulk;
 pub mod checks;
+pub mod clone;
+pub mod deploy;
+pub mod drain;
 pub mod endpoints;
+pub mod events;
+pub mod exec;
+pub mod lease;
 pub mod machine;
 pub mod networking;
 pub mod process;
+pub mod query;
 pub mod regions;
 pub mod resources;
 pub mod services;
+pub mod signal;
 pub mod system;
 
 pub use api_manager::MachineManager;
+pub use bulk::{CreateManyOptions, CreateManyReport, Replicas};
 pub use checks::{CheckKind, CheckType, Checks, Header, Protocol};
-pub use endpoints::{EventResponse, MachineRequest, MachineResponse};
-pub use machine::{MachineConfig, MachineState};
+pub use clone::{CloneOptions, ConfigOverrides};
+pub use deploy::{DeployEvent, DeployReport, RollingDeploy};
+pub use drain::DrainOptions;
+pub use endpoints::{EventResponse, MachineRequest, MachineResponse, StartResponse};
+pub use events::{MachineEvent, MachineEventKind, WatchOptions};
+pub use exec::{ExecOutput, ExecRequest};
+pub use lease::{Lease, LeaseGuard, LEASE_NONCE_HEADER};
+pub use machine::{MachineConfig, MachineLifecycleState, MachineState};
 pub use networking::{DnsConfig, DnsForwardRule};
 pub use process::{
     CommandResponse, EnvVarConfig, FieldRefEnum, ProcessConfig, ProcessResponse, SecretConfig,
 };
+pub use query::{ListMachinesQuery, PROCESS_GROUP_METADATA_KEY};
 pub use regions::MachineRegions;
-pub use resources::{CpuKind, GpuKind, GuestConfig, RestartPolicy, RestartPolicyEnum};
+pub use resources::{
+    CpuKind, GpuKind, GuestConfig, MachineSize, Preset, RestartPolicy, RestartPolicyEnum,
+};
 pub use services::ServiceConfig;
+pub use signal::{Signal, StopOptions};
 pub use system::{FileConfig, InitConfig, MetricsConfig, MountConfig, StaticConfig, StopConfig};
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 pub struct TimeoutConfig {
     pub duration: u64,
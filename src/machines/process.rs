@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ProcessConfig {
     pub cmd: Option<Vec<String>>,
     pub entrypoint: Option<Vec<String>>,
@@ -13,13 +13,13 @@ pub struct ProcessConfig {
     pub user: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EnvVarConfig {
     pub env_var: String,
     pub field_ref: FieldRefEnum,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum FieldRefEnum {
     Id,
@@ -30,13 +30,13 @@ pub enum FieldRefEnum {
     Image,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SecretConfig {
     pub env_var: String,
     pub name: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CommandResponse {
     pub exit_code: Option<i32>,
     pub exit_signal: Option<i32>,
@@ -44,7 +44,7 @@ pub struct CommandResponse {
     pub stdout: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ProcessResponse {
     pub command: Option<String>,
     pub cpu: Option<u32>,
@@ -56,7 +56,7 @@ pub struct ProcessResponse {
     pub stime: Option<u64>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ListenSocket {
     pub address: Option<String>,
     pub proto: Option<String>,
@@ -0,0 +1,118 @@
+use crate::machines::{MachineLifecycleState, MachineRegions, MachineResponse};
+use std::sync::Arc;
+
+/// Metadata key Fly sets to the process group a machine runs.
+pub const PROCESS_GROUP_METADATA_KEY: &str = "fly_process_group";
+
+type MachineFilter = Arc<dyn Fn(&MachineResponse) -> bool + Send + Sync>;
+
+/// Narrows [`MachineManager::list_with`](crate::machines::MachineManager::list_with).
+///
+/// Everything but [`ListMachinesQuery::filter`] is applied by the API, so only matching
+/// machines are downloaded.
+///
+/// ```rust
+/// use fly_sdk::machines::{ListMachinesQuery, MachineLifecycleState, MachineRegions};
+///
+/// let query = ListMachinesQuery::new()
+///     .region(MachineRegions::Ams)
+///     .state(MachineLifecycleState::Started)
+///     .process_group("worker")
+///     .filter(|machine| machine.name.as_deref().is_some_and(|name| name.starts_with("batch")));
+/// ```
+#[derive(Clone, Default)]
+pub struct ListMachinesQuery {
+    include_deleted: bool,
+    region: Option<MachineRegions>,
+    states: Vec<MachineLifecycleState>,
+    summary: bool,
+    metadata: Vec<(String, String)>,
+    filter: Option<MachineFilter>,
+}
+
+impl ListMachinesQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also lists destroyed machines.
+    pub fn include_deleted(mut self, include_deleted: bool) -> Self {
+        self.include_deleted = include_deleted;
+        self
+    }
+
+    pub fn region(mut self, region: MachineRegions) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// Only lists machines in this state; call it again to allow more states.
+    pub fn state(mut self, state: MachineLifecycleState) -> Self {
+        self.states.push(state);
+        self
+    }
+
+    /// Leaves out each machine's config, events and other details.
+    pub fn summary(mut self, summary: bool) -> Self {
+        self.summary = summary;
+        self
+    }
+
+    /// Only lists machines whose metadata has `key` set to `value`.
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn process_group(self, group: &str) -> Self {
+        self.metadata(PROCESS_GROUP_METADATA_KEY, group)
+    }
+
+    /// Keeps only the machines `filter` accepts, for conditions the API can't check.
+    pub fn filter(
+        mut self,
+        filter: impl Fn(&MachineResponse) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+
+    pub(crate) fn params(&self) -> Vec<(String, String)> {
+        let mut params = Vec::new();
+        if self.include_deleted {
+            params.push(("include_deleted".to_string(), "true".to_string()));
+        }
+        if let Some(region) = self
+            .region
+            .as_ref()
+            .and_then(|region| serde_json::to_value(region).ok())
+            .and_then(|region| region.as_str().map(str::to_string))
+        {
+            params.push(("region".to_string(), region));
+        }
+        if !self.states.is_empty() {
+            let states: Vec<&str> = self.states.iter().map(|state| state.as_str()).collect();
+            params.push(("state".to_string(), states.join(",")));
+        }
+        if self.summary {
+            params.push(("summary".to_string(), "true".to_string()));
+        }
+        for (key, value) in &self.metadata {
+            params.push((format!("metadata.{}", key), value.clone()));
+        }
+        params
+    }
+
+    pub(crate) fn matches(&self, machine: &MachineResponse) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter(machine))
+    }
+}
+
+impl std::fmt::Debug for ListMachinesQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ListMachinesQuery")
+            .field("params", &self.params())
+            .field("filter", &self.filter.is_some())
+            .finish()
+    }
+}
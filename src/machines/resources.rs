@@ -1,6 +1,7 @@
+use crate::error::FlyError;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GuestConfig {
     pub cpu_kind: Option<CpuKind>,
     pub cpus: Option<u64>,
@@ -23,11 +24,116 @@ impl Default for GuestConfig {
     }
 }
 
+impl GuestConfig {
+    /// Checks the CPU kind, CPU count and memory against the sizes Fly allows: 1, 2, 4 or 8
+    /// shared CPUs with 256MB to 2GB of memory each, or 1 to 16 performance CPUs with 2GB
+    /// to 8GB each, in steps of 256MB.
+    pub fn validate(&self) -> Result<(), FlyError> {
+        let kind = self.cpu_kind.clone().unwrap_or(CpuKind::Shared);
+        let cpus = self.cpus.unwrap_or(1);
+        let (allowed_cpus, memory_per_cpu): (&[u64], (u64, u64)) = match kind {
+            CpuKind::Shared => (&[1, 2, 4, 8], (256, 2048)),
+            CpuKind::Performance => (&[1, 2, 4, 8, 16], (2048, 8192)),
+        };
+        if !allowed_cpus.contains(&cpus) {
+            return Err(FlyError::Config(format!(
+                "{} machines can't have {} CPUs, only {:?}",
+                kind, cpus, allowed_cpus
+            )));
+        }
+
+        let (min_memory, max_memory) = (memory_per_cpu.0 * cpus, memory_per_cpu.1 * cpus);
+        let memory = self.memory_mb.unwrap_or(min_memory);
+        if memory < min_memory || memory > max_memory || !memory.is_multiple_of(256) {
+            return Err(FlyError::Config(format!(
+                "{} machines with {} CPUs need {} to {}MB of memory in steps of 256MB, not {}MB",
+                kind, cpus, min_memory, max_memory, memory
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Named machine sizes, as in `fly scale vm`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    SharedCpu1x,
+    SharedCpu2x,
+    SharedCpu4x,
+    SharedCpu8x,
+    Performance1x,
+    Performance2x,
+    Performance4x,
+    Performance8x,
+    Performance16x,
+}
+
+impl From<Preset> for GuestConfig {
+    fn from(preset: Preset) -> Self {
+        let (cpu_kind, cpus, memory_mb) = match preset {
+            Preset::SharedCpu1x => (CpuKind::Shared, 1, 256),
+            Preset::SharedCpu2x => (CpuKind::Shared, 2, 512),
+            Preset::SharedCpu4x => (CpuKind::Shared, 4, 1024),
+            Preset::SharedCpu8x => (CpuKind::Shared, 8, 2048),
+            Preset::Performance1x => (CpuKind::Performance, 1, 2048),
+            Preset::Performance2x => (CpuKind::Performance, 2, 4096),
+            Preset::Performance4x => (CpuKind::Performance, 4, 8192),
+            Preset::Performance8x => (CpuKind::Performance, 8, 16384),
+            Preset::Performance16x => (CpuKind::Performance, 16, 32768),
+        };
+        GuestConfig {
+            cpu_kind: Some(cpu_kind),
+            cpus: Some(cpus),
+            memory_mb: Some(memory_mb),
+            ..GuestConfig::default()
+        }
+    }
+}
+
+/// The size [`MachineManager::resize`](crate::machines::MachineManager::resize) gives a
+/// machine, from a [`Preset`] or a [`GuestConfig`].
+#[derive(Debug, Clone)]
+pub enum MachineSize {
+    /// Replaces the whole guest config, so GPUs and kernel args are cleared.
+    Preset(Preset),
+    /// Changes only the fields that are set; those left as `None` keep their current value.
+    Custom(GuestConfig),
+}
+
+impl MachineSize {
+    /// The guest config a machine currently sized as `current` gets.
+    pub(crate) fn apply(self, current: GuestConfig) -> GuestConfig {
+        match self {
+            MachineSize::Preset(preset) => preset.into(),
+            MachineSize::Custom(size) => GuestConfig {
+                cpu_kind: size.cpu_kind.or(current.cpu_kind),
+                cpus: size.cpus.or(current.cpus),
+                gpu_kind: size.gpu_kind.or(current.gpu_kind),
+                gpus: size.gpus.or(current.gpus),
+                memory_mb: size.memory_mb.or(current.memory_mb),
+                kernel_args: size.kernel_args.or(current.kernel_args),
+            },
+        }
+    }
+}
+
+impl From<Preset> for MachineSize {
+    fn from(preset: Preset) -> Self {
+        MachineSize::Preset(preset)
+    }
+}
+
+impl From<GuestConfig> for MachineSize {
+    fn from(size: GuestConfig) -> Self {
+        MachineSize::Custom(size)
+    }
+}
+
 /// Enum representing different kinds of CPU configurations for machines.
 ///
 /// ### For detailed pricing information:
 /// - Please refer to [Fly.io's pricing page](https://fly.io/docs/about/pricing/)
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum CpuKind {
     // /// Shared CPU with 1 vCPU.
@@ -68,11 +174,20 @@ pub enum CpuKind {
     Performance,
 }
 
+impl std::fmt::Display for CpuKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CpuKind::Shared => write!(f, "shared"),
+            CpuKind::Performance => write!(f, "performance"),
+        }
+    }
+}
+
 /// Enum representing different kinds of GPU configurations for machines.
 ///
 /// ### For detailed pricing information:
 /// - Please refer to [Fly.io's pricing page](https://fly.io/docs/about/pricing/)
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum GpuKind {
     A10,
@@ -86,7 +201,7 @@ pub enum GpuKind {
     A10080GSXM,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RestartPolicy {
     pub gpu_bid_price: Option<f64>,
     pub max_retries: Option<u32>,
@@ -103,8 +218,8 @@ impl Default for RestartPolicy {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "lowercase")]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
 pub enum RestartPolicyEnum {
     No,
     Always,
@@ -112,7 +227,7 @@ pub enum RestartPolicyEnum {
     SpotPrice,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FileConfig {
     pub guest_path: String,
     pub mode: Option<u32>,
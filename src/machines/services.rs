@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ServiceConfig {
     pub autostart: Option<bool>,
     pub autostop: Option<String>,
@@ -10,7 +10,7 @@ pub struct ServiceConfig {
     pub internal_port: Option<u16>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum AutostopEnum {
     Off,
@@ -18,21 +18,21 @@ pub enum AutostopEnum {
     Suspend,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ConcurrencyConfig {
     pub hard_limit: Option<u32>,
     pub soft_limit: Option<u32>,
     pub concurrency_type: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum ConcurrencyTypeEnum {
     Connections,
     Requests,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MachinePort {
     pub end_port: Option<u16>,
     pub force_https: Option<bool>,
@@ -43,7 +43,7 @@ pub struct MachinePort {
     pub tls_options: Option<TlsOptions>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct HttpOptions {
     pub compress: Option<bool>,
     pub h2_backend: Option<bool>,
@@ -51,18 +51,18 @@ pub struct HttpOptions {
     pub idle_timeout: Option<u64>,
     pub response: Option<ResponseOptions>,
 }
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ResponseOptions {
     pub headers: Option<HashMap<String, String>>,
     pub pristine: Option<bool>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ProxyProtoOptions {
     pub version: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TlsOptions {
     pub alpn: Option<Vec<String>>,
     pub default_self_signed: Option<bool>,
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Signals the Machines API can deliver to a machine's main process.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    SIGABRT,
+    SIGALRM,
+    SIGFPE,
+    SIGHUP,
+    SIGILL,
+    SIGINT,
+    SIGKILL,
+    SIGPIPE,
+    SIGQUIT,
+    SIGSEGV,
+    SIGTERM,
+    SIGTRAP,
+    SIGUSR1,
+}
+
+impl std::fmt::Display for Signal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let signal_str = match self {
+            Signal::SIGABRT => "SIGABRT",
+            Signal::SIGALRM => "SIGALRM",
+            Signal::SIGFPE => "SIGFPE",
+            Signal::SIGHUP => "SIGHUP",
+            Signal::SIGILL => "SIGILL",
+            Signal::SIGINT => "SIGINT",
+            Signal::SIGKILL => "SIGKILL",
+            Signal::SIGPIPE => "SIGPIPE",
+            Signal::SIGQUIT => "SIGQUIT",
+            Signal::SIGSEGV => "SIGSEGV",
+            Signal::SIGTERM => "SIGTERM",
+            Signal::SIGTRAP => "SIGTRAP",
+            Signal::SIGUSR1 => "SIGUSR1",
+        };
+        write!(f, "{}", signal_str)
+    }
+}
+
+/// How [`MachineManager::stop_with`](crate::machines::MachineManager::stop_with) stops a
+/// machine. Unset fields fall back to the machine's `stop_config`, then Fly's defaults
+/// (`SIGINT` and a 5 second grace period).
+#[derive(Debug, Clone, Default)]
+pub struct StopOptions {
+    /// Signal sent to the main process.
+    pub signal: Option<Signal>,
+    /// How long to wait after the signal before killing the machine.
+    pub timeout: Option<Duration>,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct StopRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal: Option<Signal>,
+    /// A Go duration string such as `30s`, as the API expects.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+}
+
+impl From<&StopOptions> for StopRequest {
+    fn from(options: &StopOptions) -> Self {
+        Self {
+            signal: options.signal,
+            timeout: options.timeout.map(|timeout| {
+                if timeout.subsec_millis() == 0 {
+                    format!("{}s", timeout.as_secs())
+                } else {
+                    format!("{}ms", timeout.as_millis())
+                }
+            }),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct SignalRequest {
+    pub signal: Signal,
+}
@@ -0,0 +1,67 @@
+//! Hooks that run around every HTTP request the SDK sends.
+//!
+//! Middleware is registered on [`FlyControlBuilder::middleware`](crate::FlyControlBuilder::middleware)
+//! and runs for each attempt of each request, in registration order:
+//!
+//! ```rust
+//! use fly_sdk::middleware::SetHeader;
+//! use fly_sdk::FlyControl;
+//!
+//! # fn main() -> Result<(), fly_sdk::FlyError> {
+//! let fly = FlyControl::builder()
+//!     .api_token("your_api_token")
+//!     .middleware(SetHeader::new("fly-prefer-region", "ams")?)
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::FlyError;
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::{Request, Response};
+
+/// Request and response hooks for cross-cutting behavior such as custom headers, request
+/// signing, auditing or fault injection.
+pub trait Middleware: Send + Sync {
+    /// Called before each attempt is sent. Returning an error aborts the request with it.
+    fn on_request(&self, request: &mut Request) -> Result<(), FlyError> {
+        let _ = request;
+        Ok(())
+    }
+
+    /// Called with the response of each attempt, before the SDK reads its body.
+    fn on_response(&self, request: &Request, response: &Response) {
+        let _ = (request, response);
+    }
+
+    /// Called when an attempt fails without a response.
+    fn on_error(&self, request: &Request, error: &reqwest::Error) {
+        let _ = (request, error);
+    }
+}
+
+/// Adds a fixed header to every request, e.g. `fly-prefer-region`.
+#[derive(Debug, Clone)]
+pub struct SetHeader {
+    name: HeaderName,
+    value: HeaderValue,
+}
+
+impl SetHeader {
+    pub fn new(name: &str, value: &str) -> Result<Self, FlyError> {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| FlyError::Config(format!("invalid header name: {}", name)))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|_| FlyError::Config(format!("invalid value for header {}", name)))?;
+        Ok(Self { name, value })
+    }
+}
+
+impl Middleware for SetHeader {
+    fn on_request(&self, request: &mut Request) -> Result<(), FlyError> {
+        request
+            .headers_mut()
+            .insert(self.name.clone(), self.value.clone());
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::debug;
+
+/// Kinds of Machines API calls that Fly rate-limits separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitAction {
+    Create,
+    Update,
+    /// Start, stop, restart, suspend and signal.
+    StartStop,
+    /// Get, list and wait.
+    Read,
+    /// Anything else, such as delete and exec.
+    Other,
+}
+
+/// Sustained rate and burst size of a token bucket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limit {
+    pub per_second: f64,
+    pub burst: u32,
+}
+
+impl Limit {
+    pub fn new(per_second: f64, burst: u32) -> Self {
+        Self {
+            per_second: per_second.max(0.001),
+            burst: burst.max(1),
+        }
+    }
+}
+
+/// Per-action limits used by the client-side rate limiter.
+///
+/// The defaults follow Fly's documented Machines API limits: 1 request per second with
+/// bursts of 3 for each mutating action, and 5 requests per second with bursts of 10 for
+/// reads. Actions without a limit are not throttled.
+#[derive(Debug, Clone)]
+pub struct RateLimits {
+    limits: HashMap<RateLimitAction, Limit>,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self::unlimited()
+            .limit(RateLimitAction::Create, Limit::new(1.0, 3))
+            .limit(RateLimitAction::Update, Limit::new(1.0, 3))
+            .limit(RateLimitAction::StartStop, Limit::new(1.0, 3))
+            .limit(RateLimitAction::Other, Limit::new(1.0, 3))
+            .limit(RateLimitAction::Read, Limit::new(5.0, 10))
+    }
+}
+
+impl RateLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// No limits; every request is sent immediately.
+    pub fn unlimited() -> Self {
+        Self {
+            limits: HashMap::new(),
+        }
+    }
+
+    pub fn limit(mut self, action: RateLimitAction, limit: Limit) -> Self {
+        self.limits.insert(action, limit);
+        self
+    }
+
+    pub fn remove_limit(mut self, action: RateLimitAction) -> Self {
+        self.limits.remove(&action);
+        self
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Token buckets keyed by action, shared by every clone of the limiter.
+#[derive(Debug, Clone)]
+pub(crate) struct RateLimiter {
+    limits: Arc<RateLimits>,
+    buckets: Arc<Mutex<HashMap<RateLimitAction, Bucket>>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limits: RateLimits) -> Self {
+        Self {
+            limits: Arc::new(limits),
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Waits until a request for `action` may be sent.
+    ///
+    /// Tokens are reserved up front, so concurrent callers queue in the order they arrive
+    /// instead of racing for the next refill.
+    pub(crate) async fn acquire(&self, action: RateLimitAction) {
+        let Some(limit) = self.limits.limits.get(&action).copied() else {
+            return;
+        };
+
+        let wait = {
+            let mut buckets = self.buckets.lock().unwrap_or_else(|err| err.into_inner());
+            let now = Instant::now();
+            let bucket = buckets.entry(action).or_insert(Bucket {
+                tokens: f64::from(limit.burst),
+                updated_at: now,
+            });
+
+            let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+            bucket.tokens =
+                (bucket.tokens + elapsed * limit.per_second).min(f64::from(limit.burst));
+            bucket.updated_at = now;
+            bucket.tokens -= 1.0;
+
+            if bucket.tokens < 0.0 {
+                Duration::from_secs_f64(-bucket.tokens / limit.per_second)
+            } else {
+                Duration::ZERO
+            }
+        };
+
+        if !wait.is_zero() {
+            debug!("Rate limiting {:?} request for {:?}", action, wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(RateLimits::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(per_second: f64, burst: u32) -> RateLimiter {
+        RateLimiter::new(
+            RateLimits::unlimited().limit(RateLimitAction::Create, Limit::new(per_second, burst)),
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bursts_then_queues_at_the_configured_rate() {
+        let limiter = limiter(2.0, 3);
+        let started_at = Instant::now();
+
+        for _ in 0..3 {
+            limiter.acquire(RateLimitAction::Create).await;
+        }
+        assert_eq!(started_at.elapsed(), Duration::ZERO);
+
+        limiter.acquire(RateLimitAction::Create).await;
+        assert_eq!(started_at.elapsed(), Duration::from_millis(500));
+        limiter.acquire(RateLimitAction::Create).await;
+        assert_eq!(started_at.elapsed(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_time_refills_up_to_the_burst() {
+        let limiter = limiter(1.0, 2);
+        limiter.acquire(RateLimitAction::Create).await;
+        limiter.acquire(RateLimitAction::Create).await;
+
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        let started_at = Instant::now();
+        limiter.acquire(RateLimitAction::Create).await;
+        limiter.acquire(RateLimitAction::Create).await;
+        assert_eq!(started_at.elapsed(), Duration::ZERO);
+        limiter.acquire(RateLimitAction::Create).await;
+        assert_eq!(started_at.elapsed(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn clones_share_their_buckets() {
+        let limiter = limiter(1.0, 1);
+        let clone = limiter.clone();
+        let started_at = Instant::now();
+
+        limiter.acquire(RateLimitAction::Create).await;
+        clone.acquire(RateLimitAction::Create).await;
+
+        assert_eq!(started_at.elapsed(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn actions_without_a_limit_are_not_throttled() {
+        let limiter = limiter(1.0, 1);
+        let started_at = Instant::now();
+
+        for _ in 0..10 {
+            limiter.acquire(RateLimitAction::Read).await;
+        }
+        limiter.acquire(RateLimitAction::Create).await;
+
+        assert_eq!(started_at.elapsed(), Duration::ZERO);
+    }
+}
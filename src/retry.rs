@@ -0,0 +1,230 @@
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Method, Response, StatusCode};
+use std::time::{Duration, SystemTime};
+
+/// Controls how failed requests are retried.
+///
+/// By default only idempotent requests (`GET`, `PUT`, `DELETE`, ...) are retried, on
+/// `408`, `412`, `429` and `5xx` responses and on connection failures. Enabling
+/// [`RetryPolicy::retry_mutations`] also retries the `POST` calls that are safe to
+/// repeat: starting, stopping, suspending, restarting, signalling, cordoning and
+/// uncordoning a machine. Creating resources, running commands and acquiring leases
+/// are never retried, since a lost response could otherwise launch duplicates. A
+/// `Retry-After` header, in seconds or as a date, replaces the backoff but never exceeds
+/// its maximum.
+///
+/// ```rust
+/// use fly_sdk::{FlyControl, RetryPolicy};
+/// use std::time::Duration;
+///
+/// let fly = FlyControl::builder()
+///     .api_token("your_api_token")
+///     .retry_policy(
+///         RetryPolicy::new()
+///             .max_attempts(5)
+///             .backoff(Duration::from_millis(250), Duration::from_secs(10)),
+///     )
+///     .build()
+///     .expect("valid configuration");
+///
+/// // retry starts and stops as well, since starting a machine twice is harmless
+/// let machines = fly
+///     .machines
+///     .clone()
+///     .with_retry_policy(RetryPolicy::new().retry_mutations(true));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    retry_statuses: Vec<StatusCode>,
+    retry_mutations: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(10),
+            retry_statuses: vec![
+                StatusCode::REQUEST_TIMEOUT,
+                StatusCode::PRECONDITION_FAILED,
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            retry_mutations: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A policy that sends every request exactly once.
+    pub fn disabled() -> Self {
+        Self::default().max_attempts(1)
+    }
+
+    /// Total number of attempts, including the first one.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Exponential backoff starting at `initial` and doubling up to `max`, with jitter.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    pub fn retry_statuses(mut self, statuses: Vec<StatusCode>) -> Self {
+        self.retry_statuses = statuses;
+        self
+    }
+
+    /// Also retries the `POST` calls that are safe to repeat, such as starting or
+    /// stopping a machine.
+    pub fn retry_mutations(mut self, retry_mutations: bool) -> Self {
+        self.retry_mutations = retry_mutations;
+        self
+    }
+
+    pub(crate) fn attempt_limit(&self) -> u32 {
+        self.max_attempts
+    }
+
+    pub(crate) fn retries_status(&self, status: StatusCode) -> bool {
+        self.retry_statuses.contains(&status)
+    }
+
+    /// Whether a request may be retried; `repeatable` marks a `POST` that is safe to send
+    /// again.
+    pub(crate) fn allows(&self, method: &Method, repeatable: bool) -> bool {
+        let idempotent = matches!(
+            *method,
+            Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS
+        );
+        idempotent || (repeatable && self.retry_mutations)
+    }
+
+    /// Delay before the attempt following `attempt` (1-based), honoring `Retry-After` up
+    /// to the maximum backoff.
+    pub(crate) fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_backoff);
+        }
+
+        let exponential = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff);
+        // equal jitter: wait at least half of the backoff, plus a random share of the rest
+        let half = exponential / 2;
+        half + half.mul_f64(fastrand::f64())
+    }
+}
+
+pub(crate) fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    parse_retry_after(value, SystemTime::now())
+}
+
+/// Reads `Retry-After` as either a number of seconds or an HTTP date.
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    // a date in the past means the request may be retried right away
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_backs_off_exponentially_with_jitter() {
+        let policy =
+            RetryPolicy::new().backoff(Duration::from_millis(200), Duration::from_secs(10));
+
+        for _ in 0..100 {
+            let first = policy.delay(1, None);
+            assert!(first >= Duration::from_millis(100) && first <= Duration::from_millis(200));
+            let third = policy.delay(3, None);
+            assert!(third >= Duration::from_millis(400) && third <= Duration::from_millis(800));
+            let capped = policy.delay(30, None);
+            assert!(capped >= Duration::from_secs(5) && capped <= Duration::from_secs(10));
+        }
+    }
+
+    #[test]
+    fn delay_honors_retry_after_up_to_the_max_backoff() {
+        let policy =
+            RetryPolicy::new().backoff(Duration::from_millis(200), Duration::from_secs(10));
+
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(3))),
+            Duration::from_secs(3)
+        );
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(3600))),
+            Duration::from_secs(10)
+        );
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_dates() {
+        let now = httpdate::parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+
+        assert_eq!(
+            parse_retry_after(" 120 ", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:50:07 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn only_idempotent_methods_are_retried_by_default() {
+        let policy = RetryPolicy::new();
+        for method in [Method::GET, Method::PUT, Method::DELETE, Method::HEAD] {
+            assert!(policy.allows(&method, false), "{method}");
+        }
+        assert!(!policy.allows(&Method::POST, false));
+        assert!(!policy.allows(&Method::POST, true));
+        assert!(!policy.allows(&Method::PATCH, false));
+    }
+
+    #[test]
+    fn retry_mutations_only_covers_repeatable_calls() {
+        let policy = RetryPolicy::new().retry_mutations(true);
+        assert!(policy.allows(&Method::POST, true));
+        assert!(!policy.allows(&Method::POST, false));
+        assert!(!policy.allows(&Method::PATCH, false));
+    }
+
+    #[test]
+    fn disabled_policy_makes_a_single_attempt() {
+        assert_eq!(RetryPolicy::disabled().attempt_limit(), 1);
+        assert_eq!(RetryPolicy::new().max_attempts(0).attempt_limit(), 1);
+        assert!(RetryPolicy::new().retries_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!RetryPolicy::new().retries_status(StatusCode::NOT_FOUND));
+    }
+}
@@ -1,7 +1,11 @@
-use crate::API_BASE_URL;
+use crate::client::{ApiClient, ApiRequest};
+use crate::error::FlyError;
+use crate::middleware::Middleware;
+use crate::retry::RetryPolicy;
+use crate::token::{StaticToken, TokenProvider};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::error::Error;
+use std::sync::Arc;
 use tracing::debug;
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -23,32 +27,52 @@ impl SecretValue {
     }
 }
 
+#[derive(Clone)]
 pub struct SecretsManager {
-    client: Client,
-    api_token: String,
+    api: ApiClient,
 }
 
 impl SecretsManager {
     pub fn new(client: Client, api_token: String) -> Self {
-        Self { client, api_token }
+        Self::from_api(ApiClient::new(
+            client,
+            Arc::new(StaticToken::new(&api_token)),
+        ))
     }
 
-    pub async fn list_secrets(&self, app_name: &str) -> Result<Vec<Secret>, Box<dyn Error>> {
-        let url = format!("{API_BASE_URL}/apps/{}/secrets", app_name);
-        let response = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.api_token)
-            .send()
-            .await?;
+    pub(crate) fn from_api(api: ApiClient) -> Self {
+        Self { api }
+    }
 
-        if response.status().is_success() {
-            let secrets = response.json::<Vec<Secret>>().await?;
-            debug!("Successfully fetched secrets: {:?}", secrets);
-            Ok(secrets)
-        } else {
-            Err(format!("Failed to fetch secrets: {}", response.status()).into())
-        }
+    /// Looks up the API token from `token_provider` for every request.
+    pub fn with_token_provider(mut self, token_provider: impl TokenProvider + 'static) -> Self {
+        self.api.set_token_provider(Arc::new(token_provider));
+        self
+    }
+
+    /// Sends requests to `base_url` instead of [`API_BASE_URL`](crate::API_BASE_URL).
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.api.set_base_url(base_url);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.api.set_retry_policy(retry_policy);
+        self
+    }
+
+    pub fn with_middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.api.push_middleware(Arc::new(middleware));
+        self
+    }
+
+    pub async fn list_secrets(&self, app_name: &str) -> Result<Vec<Secret>, FlyError> {
+        let secrets: Vec<Secret> = self
+            .api
+            .json(ApiRequest::get("/apps/{app}/secrets").param("app", app_name))
+            .await?;
+        debug!("Successfully fetched secrets: {:?}", secrets);
+        Ok(secrets)
     }
 
     pub async fn create_secret(
@@ -57,28 +81,15 @@ impl SecretsManager {
         secret_label: &str,
         secret_type: &str,
         value_request: SecretValue,
-    ) -> Result<Secret, Box<dyn Error>> {
+    ) -> Result<Secret, FlyError> {
         debug!("Creating secret: {}", secret_label);
-        let url = format!(
-            "{API_BASE_URL}/apps/{}/secrets/{}/type/{}",
-            app_name, secret_label, secret_type
-        );
-        let response = self
-            .client
-            .post(&url)
-            .bearer_auth(&self.api_token)
-            .json(&value_request)
-            .send()
-            .await?;
+        let request = ApiRequest::post("/apps/{app}/secrets/{secret}/type/{type}")
+            .param("app", app_name)
+            .param("secret", secret_label)
+            .param("type", secret_type)
+            .json(&value_request);
 
-        let status = response.status();
-        if status.is_success() {
-            let secret = response.json::<Secret>().await?;
-            Ok(secret)
-        } else {
-            let error_text = response.text().await?;
-            Err(format!("Failed to create secret: {} - {}", status, error_text).into())
-        }
+        self.api.json(request).await
     }
 
     pub async fn generate_secret(
@@ -86,46 +97,26 @@ impl SecretsManager {
         app_name: &str,
         secret_label: &str,
         secret_type: &str,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), FlyError> {
         debug!("Generating secret: {}", secret_label);
-        let url = format!(
-            "{API_BASE_URL}/apps/{}/secrets/{}/type/{}/generate",
-            app_name, secret_label, secret_type
-        );
-        let response = self
-            .client
-            .post(&url)
-            .bearer_auth(&self.api_token)
-            .send()
-            .await?;
+        let request = ApiRequest::post("/apps/{app}/secrets/{secret}/type/{type}/generate")
+            .param("app", app_name)
+            .param("secret", secret_label)
+            .param("type", secret_type);
 
-        if response.status().is_success() {
-            debug!("Successfully generated secret: {}", secret_label);
-            Ok(())
-        } else {
-            Err(format!("Failed to generate secret: {}", response.status()).into())
-        }
+        self.api.execute(request).await?;
+        debug!("Successfully generated secret: {}", secret_label);
+        Ok(())
     }
 
-    pub async fn destroy_secret(
-        &self,
-        app_name: &str,
-        secret_label: &str,
-    ) -> Result<(), Box<dyn Error>> {
+    pub async fn destroy_secret(&self, app_name: &str, secret_label: &str) -> Result<(), FlyError> {
         debug!("Deleting secret: {}", secret_label);
-        let url = format!("{API_BASE_URL}/apps/{}/secrets/{}", app_name, secret_label);
-        let response = self
-            .client
-            .delete(&url)
-            .bearer_auth(&self.api_token)
-            .send()
-            .await?;
+        let request = ApiRequest::delete("/apps/{app}/secrets/{secret}")
+            .param("app", app_name)
+            .param("secret", secret_label);
 
-        if response.status().is_success() {
-            debug!("Successfully deleted secret: {}", secret_label);
-            Ok(())
-        } else {
-            Err(format!("Failed to delete secret: {}", response.status()).into())
-        }
+        self.api.execute(request).await?;
+        debug!("Successfully deleted secret: {}", secret_label);
+        Ok(())
     }
 }
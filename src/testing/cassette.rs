@@ -0,0 +1,430 @@
+use crate::error::{FlyError, REQUEST_ID_HEADER};
+use axum::body::{to_bytes, Body};
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::task::JoinHandle;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// JSON fields whose values are never written to a cassette.
+const DEFAULT_REDACTED_FIELDS: &[&str] = &["value", "token", "access_token", "password"];
+
+/// Response headers kept in a cassette; everything else is dropped.
+const RECORDED_HEADERS: &[&str] = &["content-type", REQUEST_ID_HEADER, "retry-after"];
+
+/// Largest request or response body the proxy buffers.
+const MAX_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+/// A recorded request/response pair.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Path and query relative to the API base URL, e.g. `/apps/my-app/machines`.
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// The decoded JSON body, or the raw text for non-JSON responses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+/// Configures a cassette file before starting a [`CassetteServer`] for it.
+///
+/// ```rust,no_run
+/// use fly_sdk::testing::Cassette;
+/// use fly_sdk::{FlyControl, API_BASE_URL};
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), fly_sdk::FlyError> {
+/// // once, against the real API
+/// let server = Cassette::new("tests/cassettes/list_apps.json")
+///     .redact_field("DATABASE_URL")
+///     .record(API_BASE_URL)
+///     .await?;
+/// let fly = FlyControl::builder()
+///     .api_token("your_api_token")
+///     .base_url(&server.base_url())
+///     .build()?;
+/// fly.apps.list("personal").await?;
+///
+/// // in tests, without network access
+/// let server = Cassette::new("tests/cassettes/list_apps.json").replay().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Cassette {
+    path: PathBuf,
+    redacted_fields: Vec<String>,
+}
+
+impl Cassette {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            redacted_fields: DEFAULT_REDACTED_FIELDS
+                .iter()
+                .map(|field| field.to_string())
+                .collect(),
+        }
+    }
+
+    /// Also redacts the values of JSON fields named `field`, at any depth.
+    ///
+    /// `value`, `token`, `access_token` and `password` are always redacted, as is the
+    /// API token itself wherever it appears.
+    pub fn redact_field(mut self, field: &str) -> Self {
+        self.redacted_fields.push(field.to_string());
+        self
+    }
+
+    /// Starts a proxy that forwards every request to `upstream`. The cassette file is
+    /// truncated when recording starts, then each exchange is appended to it as it happens.
+    pub async fn record(self, upstream: &str) -> Result<CassetteServer, FlyError> {
+        let mode = Mode::Record {
+            upstream: upstream.trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+        };
+        let server = CassetteServer::start(self, mode, Vec::new()).await?;
+        let saved = server.shared.save(&server.shared.lock());
+        saved.map_err(|err| {
+            FlyError::Config(format!(
+                "failed to write {}: {}",
+                server.shared.cassette.path.display(),
+                err
+            ))
+        })?;
+        Ok(server)
+    }
+
+    /// Starts a server that answers from the cassette file without touching the network.
+    pub async fn replay(self) -> Result<CassetteServer, FlyError> {
+        let contents = std::fs::read_to_string(&self.path).map_err(|err| {
+            FlyError::Config(format!("failed to read {}: {}", self.path.display(), err))
+        })?;
+        let interactions: Vec<Interaction> = serde_json::from_str(&contents).map_err(|err| {
+            FlyError::Config(format!("invalid cassette {}: {}", self.path.display(), err))
+        })?;
+        CassetteServer::start(self, Mode::Replay, interactions).await
+    }
+}
+
+/// A local server recording or replaying a [`Cassette`]; point the SDK at
+/// [`CassetteServer::base_url`].
+///
+/// When replaying, requests are matched on method, path and query. Matching
+/// interactions are served in recorded order and the last one is repeated once they
+/// run out, so polling calls keep working. Unmatched requests get a `501`.
+pub struct CassetteServer {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+    server: JoinHandle<()>,
+}
+
+struct Shared {
+    cassette: Cassette,
+    mode: Mode,
+    state: Mutex<CassetteState>,
+}
+
+enum Mode {
+    Record {
+        upstream: String,
+        http: reqwest::Client,
+    },
+    Replay,
+}
+
+struct CassetteState {
+    interactions: Vec<Interaction>,
+    played: Vec<bool>,
+}
+
+impl CassetteServer {
+    async fn start(
+        cassette: Cassette,
+        mode: Mode,
+        interactions: Vec<Interaction>,
+    ) -> Result<Self, FlyError> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(|err| FlyError::Config(format!("failed to bind cassette server: {}", err)))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|err| FlyError::Config(format!("failed to bind cassette server: {}", err)))?;
+
+        let shared = Arc::new(Shared {
+            cassette,
+            mode,
+            state: Mutex::new(CassetteState {
+                played: vec![false; interactions.len()],
+                interactions,
+            }),
+        });
+        let app = Router::new().fallback(handle).with_state(shared.clone());
+        let server = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+
+        Ok(Self {
+            addr,
+            shared,
+            server,
+        })
+    }
+
+    /// Base URL to pass to [`FlyControlBuilder::base_url`](crate::FlyControlBuilder::base_url).
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Interactions recorded so far, or loaded from the cassette when replaying.
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.shared.lock().interactions.clone()
+    }
+}
+
+impl Drop for CassetteServer {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, CassetteState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Writes the cassette after every recorded exchange, so nothing is lost if the test
+    /// that drives the recording fails halfway.
+    fn save(&self, state: &CassetteState) -> Result<(), String> {
+        let path = &self.cassette.path;
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+        let contents =
+            serde_json::to_string_pretty(&state.interactions).map_err(|err| err.to_string())?;
+        std::fs::write(path, contents + "\n").map_err(|err| err.to_string())
+    }
+
+    fn redact(&self, mut body: Value, secrets: &[String]) -> Value {
+        redact_value(&mut body, &self.cassette.redacted_fields, secrets);
+        body
+    }
+}
+
+async fn handle(State(shared): State<Arc<Shared>>, request: Request) -> Response {
+    let (parts, body) = request.into_parts();
+    let path = parts
+        .uri
+        .path_and_query()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| parts.uri.path().to_string());
+    let body = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(err) => return error(StatusCode::BAD_REQUEST, &err.to_string()),
+    };
+
+    match &shared.mode {
+        Mode::Replay => replay(&shared, &parts.method, &path),
+        Mode::Record { upstream, http } => {
+            let url = format!("{}{}", upstream, path);
+            let mut forwarded = http.request(parts.method.clone(), url);
+            for (name, value) in parts.headers.iter() {
+                if name != "host" && name != "content-length" {
+                    forwarded = forwarded.header(name, value);
+                }
+            }
+            if !body.is_empty() {
+                forwarded = forwarded.body(body.clone());
+            }
+            let upstream_response = match forwarded.send().await {
+                Ok(response) => response,
+                Err(err) => return error(StatusCode::BAD_GATEWAY, &err.to_string()),
+            };
+
+            let status = upstream_response.status();
+            let headers = recorded_headers(upstream_response.headers());
+            let reply_headers = headers_to_send(&headers);
+            let response_body = match upstream_response.bytes().await {
+                Ok(body) => body,
+                Err(err) => return error(StatusCode::BAD_GATEWAY, &err.to_string()),
+            };
+
+            let secrets = bearer_token(&parts.headers).into_iter().collect::<Vec<_>>();
+            let interaction = Interaction {
+                request: RecordedRequest {
+                    method: parts.method.to_string(),
+                    path,
+                    body: decode_body(&body).map(|body| shared.redact(body, &secrets)),
+                },
+                response: RecordedResponse {
+                    status: status.as_u16(),
+                    headers,
+                    body: decode_body(&response_body).map(|body| shared.redact(body, &secrets)),
+                },
+            };
+
+            let mut state = shared.lock();
+            state.interactions.push(interaction);
+            state.played.push(true);
+            if let Err(err) = shared.save(&state) {
+                return error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    &format!("failed to write cassette: {}", err),
+                );
+            }
+            drop(state);
+
+            let mut response = Response::new(Body::from(response_body));
+            *response.status_mut() = status;
+            for (name, value) in reply_headers {
+                response.headers_mut().insert(name, value);
+            }
+            response
+        }
+    }
+}
+
+fn replay(shared: &Shared, method: &Method, path: &str) -> Response {
+    let mut state = shared.lock();
+    let matching: Vec<usize> = state
+        .interactions
+        .iter()
+        .enumerate()
+        .filter(|(_, interaction)| {
+            interaction.request.method == method.as_str() && interaction.request.path == path
+        })
+        .map(|(index, _)| index)
+        .collect();
+
+    let Some(index) = matching
+        .iter()
+        .copied()
+        .find(|&index| !state.played[index])
+        .or_else(|| matching.last().copied())
+    else {
+        return error(
+            StatusCode::NOT_IMPLEMENTED,
+            &format!("no recorded interaction for {} {}", method, path),
+        );
+    };
+    state.played[index] = true;
+
+    let recorded = &state.interactions[index];
+    let status = StatusCode::from_u16(recorded.response.status).unwrap_or(StatusCode::OK);
+    let is_json = recorded
+        .response
+        .headers
+        .get("content-type")
+        .is_none_or(|content_type| content_type.contains("json"));
+    let body = match &recorded.response.body {
+        None => Vec::new(),
+        Some(Value::String(text)) if !is_json => text.clone().into_bytes(),
+        Some(body) => serde_json::to_vec(body).unwrap_or_default(),
+    };
+
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    for (name, value) in headers_to_send(&recorded.response.headers) {
+        response.headers_mut().insert(name, value);
+    }
+    response
+}
+
+fn redact_value(value: &mut Value, fields: &[String], secrets: &[String]) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if fields.iter().any(|field| field == key) {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact_value(value, fields, secrets);
+                }
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                redact_value(value, fields, secrets);
+            }
+        }
+        Value::String(text) => {
+            for secret in secrets {
+                if text.contains(secret.as_str()) {
+                    *text = text.replace(secret.as_str(), REDACTED);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The credential part of the `Authorization` header, whichever scheme it uses.
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get("authorization")?.to_str().ok()?;
+    let token = value
+        .strip_prefix("Bearer ")
+        .or_else(|| value.strip_prefix("FlyV1 "))
+        .unwrap_or(value)
+        .trim();
+    (!token.is_empty()).then(|| token.to_string())
+}
+
+fn decode_body(body: &[u8]) -> Option<Value> {
+    if body.is_empty() {
+        return None;
+    }
+    serde_json::from_slice(body)
+        .ok()
+        .or_else(|| Some(Value::String(String::from_utf8_lossy(body).into_owned())))
+}
+
+fn recorded_headers(headers: &reqwest::header::HeaderMap) -> BTreeMap<String, String> {
+    RECORDED_HEADERS
+        .iter()
+        .filter_map(|name| {
+            let value = headers.get(*name)?.to_str().ok()?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+fn headers_to_send(headers: &BTreeMap<String, String>) -> Vec<(HeaderName, HeaderValue)> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| {
+            Some((
+                HeaderName::from_bytes(name.as_bytes()).ok()?,
+                HeaderValue::from_str(value).ok()?,
+            ))
+        })
+        .collect()
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
@@ -0,0 +1,1830 @@
+use crate::machines::{Signal, LEASE_NONCE_HEADER};
+use crate::rate_limit::RateLimits;
+use crate::FlyControl;
+use axum::body::Bytes;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post, put};
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// Token accepted by a [`FakeFly`] server.
+pub const FAKE_TOKEN: &str = "fake-fly-token";
+
+/// The longest wait the Machines API allows, in seconds.
+const MAX_WAIT_SECONDS: u64 = 60;
+
+/// Result of a command run through the fake `exec` endpoint; by default it exits with 0.
+#[derive(Debug, Clone)]
+pub struct ExecReply {
+    /// `None` leaves the exit code out of the response.
+    pub exit_code: Option<i32>,
+    pub exit_signal: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl Default for ExecReply {
+    fn default() -> Self {
+        Self {
+            exit_code: Some(0),
+            exit_signal: None,
+            stdout: String::new(),
+            stderr: String::new(),
+        }
+    }
+}
+
+type ExecHandler = Arc<dyn Fn(&[String], Option<&str>) -> ExecReply + Send + Sync>;
+
+/// In-memory Machines API served on localhost.
+///
+/// Apps, machines, volumes, snapshots and secrets live in memory for as long as the
+/// server does. Machines move through `created → started → stopped → destroyed`, passing
+/// through transitional states such as `starting` and `stopping` for
+/// [`FakeFly::set_transition_delay`] each, and `/wait` blocks until the requested state
+/// is reached or its timeout expires. The server stops when the `FakeFly` is dropped.
+pub struct FakeFly {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+    server: JoinHandle<()>,
+}
+
+struct Shared {
+    state: Mutex<FakeState>,
+    changes: watch::Sender<u64>,
+}
+
+struct FakeState {
+    apps: BTreeMap<String, FakeApp>,
+    transition_delay: Duration,
+    exec_handler: ExecHandler,
+    failing_creates: usize,
+    creates_before_failures: usize,
+    unhealthy_images: HashSet<String>,
+    unbootable_images: HashSet<String>,
+    injected_failures: Vec<InjectedFailure>,
+    request_counts: HashMap<String, usize>,
+}
+
+struct InjectedFailure {
+    path_suffix: String,
+    remaining: usize,
+    status: StatusCode,
+    retry_after: Option<String>,
+}
+
+struct FakeApp {
+    id: String,
+    org_slug: String,
+    machines: BTreeMap<String, FakeMachine>,
+    volumes: BTreeMap<String, FakeVolume>,
+    secrets: BTreeMap<String, FakeSecret>,
+}
+
+struct FakeMachine {
+    id: String,
+    name: String,
+    region: String,
+    instance_id: String,
+    state: String,
+    config: Value,
+    private_ip: String,
+    created_at: String,
+    updated_at: String,
+    events: Vec<Value>,
+    lease: Option<FakeLease>,
+    cordoned: bool,
+    busy: bool,
+    cpu: u64,
+    unhealthy: bool,
+}
+
+struct FakeLease {
+    nonce: String,
+    owner: String,
+    description: String,
+    expires_at: u64,
+    version: String,
+}
+
+struct FakeVolume {
+    id: String,
+    name: String,
+    region: String,
+    size_gb: u64,
+    encrypted: bool,
+    fstype: String,
+    state: String,
+    auto_backup_enabled: bool,
+    snapshot_retention: u64,
+    attached_machine_id: Option<String>,
+    created_at: String,
+    snapshots: Vec<Value>,
+}
+
+struct FakeSecret {
+    label: String,
+    secret_type: String,
+}
+
+impl FakeFly {
+    /// Starts a server on a random local port.
+    ///
+    /// # Panics
+    /// Panics if no local port can be bound.
+    pub async fn start() -> Self {
+        let (changes, _) = watch::channel(0);
+        let shared = Arc::new(Shared {
+            state: Mutex::new(FakeState {
+                apps: BTreeMap::new(),
+                transition_delay: Duration::from_millis(20),
+                exec_handler: Arc::new(|_, _| ExecReply::default()),
+                failing_creates: 0,
+                creates_before_failures: 0,
+                unhealthy_images: HashSet::new(),
+                unbootable_images: HashSet::new(),
+                injected_failures: Vec::new(),
+                request_counts: HashMap::new(),
+            }),
+            changes,
+        });
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind FakeFly listener");
+        let addr = listener
+            .local_addr()
+            .expect("FakeFly listener has no address");
+        let app = router(shared.clone());
+        let server = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+
+        Self {
+            addr,
+            shared,
+            server,
+        }
+    }
+
+    /// Base URL to pass to [`FlyControlBuilder::base_url`](crate::FlyControlBuilder::base_url).
+    pub fn base_url(&self) -> String {
+        format!("http://{}/v1", self.addr)
+    }
+
+    /// A client pointed at this server, authenticated with [`FAKE_TOKEN`] and without
+    /// client-side rate limits.
+    pub fn client(&self) -> FlyControl {
+        FlyControl::builder()
+            .api_token(FAKE_TOKEN)
+            .base_url(&self.base_url())
+            .rate_limits(RateLimits::unlimited())
+            .build()
+            .expect("FakeFly client configuration is valid")
+    }
+
+    /// How long machines stay in transitional states such as `starting`; 20ms by default.
+    pub fn set_transition_delay(&self, delay: Duration) {
+        self.shared.lock().transition_delay = delay;
+    }
+
+    /// Replaces the handler that answers `exec` requests, which is given the command and
+    /// its stdin and by default exits with 0 and no output.
+    pub fn set_exec_handler(
+        &self,
+        handler: impl Fn(&[String], Option<&str>) -> ExecReply + Send + Sync + 'static,
+    ) {
+        self.shared.lock().exec_handler = Arc::new(handler);
+    }
+
+    /// Makes the next `count` machine creations fail as if the region were out of capacity.
+    pub fn fail_next_creates(&self, count: usize) {
+        self.fail_creates_after(0, count);
+    }
+
+    /// Lets `successes` machine creations through, then fails the `count` after them.
+    pub fn fail_creates_after(&self, successes: usize, count: usize) {
+        let mut state = self.shared.lock();
+        state.creates_before_failures = successes;
+        state.failing_creates = count;
+    }
+
+    /// Current state of a machine, including `destroyed` machines.
+    pub fn machine_state(&self, app_name: &str, machine_id: &str) -> Option<String> {
+        let state = self.shared.lock();
+        let machine = state.apps.get(app_name)?.machines.get(machine_id)?;
+        Some(machine.state.clone())
+    }
+
+    /// Whether a machine is cordoned off from the Fly proxy.
+    pub fn is_cordoned(&self, app_name: &str, machine_id: &str) -> bool {
+        let state = self.shared.lock();
+        state
+            .apps
+            .get(app_name)
+            .and_then(|app| app.machines.get(machine_id))
+            .is_some_and(|machine| machine.cordoned)
+    }
+
+    /// Makes a machine report more CPU usage on every process listing while `busy`, as if
+    /// it were still serving requests.
+    pub fn set_busy(&self, app_name: &str, machine_id: &str, busy: bool) {
+        let mut state = self.shared.lock();
+        if let Some(machine) = state
+            .apps
+            .get_mut(app_name)
+            .and_then(|app| app.machines.get_mut(machine_id))
+        {
+            machine.busy = busy;
+        }
+    }
+
+    /// Stops a machine as if its main process exited with `exit_code` on its own.
+    pub fn crash(&self, app_name: &str, machine_id: &str, exit_code: i32) {
+        let mut state = self.shared.lock();
+        if let Some(machine) = state
+            .apps
+            .get_mut(app_name)
+            .and_then(|app| app.machines.get_mut(machine_id))
+        {
+            machine.state = "stopped".to_string();
+            machine.updated_at = now_rfc3339();
+            machine.record_event(
+                "exit",
+                "stopped",
+                json!({ "exit_event": { "exit_code": exit_code, "requested_stop": false } }),
+            );
+        }
+        drop(state);
+        self.shared.notify();
+    }
+
+    /// Makes the health checks of machines running `image`, now or after an update, report
+    /// `critical`. Machines only report checks if their config has any.
+    pub fn set_unhealthy_image(&self, image: &str) {
+        let mut state = self.shared.lock();
+        state.unhealthy_images.insert(image.to_string());
+        for machine in state
+            .apps
+            .values_mut()
+            .flat_map(|app| app.machines.values_mut())
+        {
+            if machine.config.get("image").and_then(Value::as_str) == Some(image) {
+                machine.unhealthy = true;
+            }
+        }
+        drop(state);
+        self.shared.notify();
+    }
+
+    /// Makes machines running `image` end up `failed` instead of `started` whenever they
+    /// boot, as if the image's entrypoint crashed.
+    pub fn set_unbootable_image(&self, image: &str) {
+        self.shared
+            .lock()
+            .unbootable_images
+            .insert(image.to_string());
+    }
+
+    /// Answers the next `count` requests whose path ends with `path_suffix`, e.g. `/wait`,
+    /// with `status` instead of handling them, optionally sending a `Retry-After` header.
+    pub fn fail_next_requests(
+        &self,
+        path_suffix: &str,
+        count: usize,
+        status: StatusCode,
+        retry_after: Option<&str>,
+    ) {
+        self.shared.lock().injected_failures.push(InjectedFailure {
+            path_suffix: path_suffix.to_string(),
+            remaining: count,
+            status,
+            retry_after: retry_after.map(str::to_string),
+        });
+    }
+
+    /// Number of requests received so far whose path ends with `path_suffix`, including
+    /// those answered with an injected failure.
+    pub fn request_count(&self, path_suffix: &str) -> usize {
+        self.shared
+            .lock()
+            .request_counts
+            .iter()
+            .filter(|(path, _)| path.ends_with(path_suffix))
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// Forces a machine into `state`, e.g. `failed`, to simulate problems on Fly's side.
+    pub fn set_machine_state(&self, app_name: &str, machine_id: &str, new_state: &str) {
+        let mut state = self.shared.lock();
+        if let Some(machine) = state
+            .apps
+            .get_mut(app_name)
+            .and_then(|app| app.machines.get_mut(machine_id))
+        {
+            machine.state = new_state.to_string();
+            machine.updated_at = now_rfc3339();
+        }
+        drop(state);
+        self.shared.notify();
+    }
+}
+
+impl Drop for FakeFly {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, FakeState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn notify(&self) {
+        self.changes.send_modify(|version| *version += 1);
+    }
+}
+
+fn router(shared: Arc<Shared>) -> Router {
+    Router::new()
+        .route("/v1/apps", post(create_app).get(list_apps))
+        .route("/v1/apps/:app", delete(delete_app))
+        .route(
+            "/v1/apps/:app/machines",
+            post(create_machine).get(list_machines),
+        )
+        .route(
+            "/v1/apps/:app/machines/:id",
+            get(get_machine).post(update_machine).delete(delete_machine),
+        )
+        .route("/v1/apps/:app/machines/:id/start", post(start_machine))
+        .route("/v1/apps/:app/machines/:id/stop", post(stop_machine))
+        .route("/v1/apps/:app/machines/:id/suspend", post(suspend_machine))
+        .route("/v1/apps/:app/machines/:id/signal", post(signal_machine))
+        .route("/v1/apps/:app/machines/:id/cordon", post(cordon_machine))
+        .route("/v1/apps/:app/machines/:id/metadata", get(get_metadata))
+        .route(
+            "/v1/apps/:app/machines/:id/metadata/:key",
+            post(set_metadata_key).delete(delete_metadata_key),
+        )
+        .route(
+            "/v1/apps/:app/machines/:id/uncordon",
+            post(uncordon_machine),
+        )
+        .route("/v1/apps/:app/machines/:id/restart", post(restart_machine))
+        .route("/v1/apps/:app/machines/:id/wait", get(wait_machine))
+        .route("/v1/apps/:app/machines/:id/events", get(list_events))
+        .route("/v1/apps/:app/machines/:id/ps", get(list_processes))
+        .route("/v1/apps/:app/machines/:id/exec", post(exec_machine))
+        .route(
+            "/v1/apps/:app/machines/:id/lease",
+            get(get_lease).post(create_lease).delete(release_lease),
+        )
+        .route(
+            "/v1/apps/:app/volumes",
+            post(create_volume).get(list_volumes),
+        )
+        .route(
+            "/v1/apps/:app/volumes/:id",
+            get(get_volume).put(update_volume).delete(destroy_volume),
+        )
+        .route("/v1/apps/:app/volumes/:id/extend", put(extend_volume))
+        .route(
+            "/v1/apps/:app/volumes/:id/snapshots",
+            get(list_snapshots).post(create_snapshot),
+        )
+        .route("/v1/apps/:app/secrets", get(list_secrets))
+        .route("/v1/apps/:app/secrets/:label", delete(destroy_secret))
+        .route(
+            "/v1/apps/:app/secrets/:label/type/:type",
+            post(create_secret),
+        )
+        .route(
+            "/v1/apps/:app/secrets/:label/type/:type/generate",
+            post(generate_secret),
+        )
+        .layer(middleware::from_fn_with_state(
+            shared.clone(),
+            inject_failures,
+        ))
+        .layer(middleware::from_fn(authenticate))
+        .with_state(shared)
+}
+
+/// Counts every request and answers it with a failure queued by
+/// [`FakeFly::fail_next_requests`], if one matches its path.
+async fn inject_failures(State(shared): SharedState, request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    let injected = {
+        let mut state = shared.lock();
+        *state.request_counts.entry(path.clone()).or_default() += 1;
+        let failure = state
+            .injected_failures
+            .iter_mut()
+            .find(|failure| failure.remaining > 0 && path.ends_with(&failure.path_suffix));
+        failure.map(|failure| {
+            failure.remaining -= 1;
+            (failure.status, failure.retry_after.clone())
+        })
+    };
+
+    let Some((status, retry_after)) = injected else {
+        return next.run(request).await;
+    };
+    let mut response = error(status, "injected failure");
+    if let Some(value) = retry_after.and_then(|value| HeaderValue::from_str(&value).ok()) {
+        response.headers_mut().insert("retry-after", value);
+    }
+    response
+}
+
+/// Rejects requests without the fake token and tags every response with a request id.
+async fn authenticate(request: Request, next: Next) -> Response {
+    let authorized = request
+        .headers()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        == Some(format!("Bearer {}", FAKE_TOKEN).as_str());
+
+    let mut response = if authorized {
+        next.run(request).await
+    } else {
+        error(StatusCode::UNAUTHORIZED, "unauthorized")
+    };
+    if let Ok(request_id) = HeaderValue::from_str(&random_id(26)) {
+        response.headers_mut().insert("fly-request-id", request_id);
+    }
+    response
+}
+
+type SharedState = State<Arc<Shared>>;
+type Params = Query<HashMap<String, String>>;
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+fn app_not_found() -> Response {
+    error(StatusCode::NOT_FOUND, "app not found")
+}
+
+fn machine_not_found() -> Response {
+    error(StatusCode::NOT_FOUND, "machine not found")
+}
+
+fn ok() -> Response {
+    Json(json!({ "ok": true })).into_response()
+}
+
+fn random_id(len: usize) -> String {
+    const ALPHABET: &[u8] = b"0123456789abcdef";
+    (0..len)
+        .map(|_| ALPHABET[fastrand::usize(..ALPHABET.len())] as char)
+        .collect()
+}
+
+fn instance_id() -> String {
+    const ALPHABET: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+    (0..26)
+        .map(|_| ALPHABET[fastrand::usize(..ALPHABET.len())] as char)
+        .collect()
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+fn now_rfc3339() -> String {
+    let secs = now_millis() / 1000;
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    // civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+impl FakeState {
+    /// Whether the machine's image was not marked with [`FakeFly::set_unbootable_image`].
+    fn boots(&self, app_name: &str, machine_id: &str) -> bool {
+        self.apps
+            .get(app_name)
+            .and_then(|app| app.machines.get(machine_id))
+            .and_then(|machine| machine.config.get("image"))
+            .and_then(Value::as_str)
+            .is_none_or(|image| !self.unbootable_images.contains(image))
+    }
+
+    fn is_unhealthy(&self, config: &Value) -> bool {
+        config
+            .get("image")
+            .and_then(Value::as_str)
+            .is_some_and(|image| self.unhealthy_images.contains(image))
+    }
+}
+
+impl FakeMachine {
+    fn to_json(&self) -> Value {
+        let mut json = json!({
+            "id": self.id,
+            "name": self.name,
+            "state": self.state,
+            "region": self.region,
+            "instance_id": self.instance_id,
+            "private_ip": self.private_ip,
+            "config": self.config,
+            "image_ref": {
+                "registry": "registry-1.docker.io",
+                "repository": self.config.get("image").and_then(Value::as_str).unwrap_or_default(),
+                "tag": "latest",
+            },
+            "created_at": self.created_at,
+            "updated_at": self.updated_at,
+            "events": self.events.iter().take(5).collect::<Vec<_>>(),
+            "host_status": "ok",
+        });
+        if self.config.get("checks").is_some_and(Value::is_object) {
+            json["checks"] = json!([self.check_json()]);
+        }
+        json
+    }
+
+    /// A single check standing in for whatever the config defines, passing once the
+    /// machine is started unless its image was marked unhealthy.
+    fn check_json(&self) -> Value {
+        let (status, output) = if self.state != "started" {
+            ("critical", "machine is not started")
+        } else if self.unhealthy {
+            ("critical", "connection refused")
+        } else {
+            ("passing", "OK")
+        };
+        json!({
+            "name": "servicecheck-00",
+            "status": status,
+            "output": output,
+            "updated_at": self.updated_at,
+        })
+    }
+
+    fn record_event(&mut self, event_type: &str, status: &str, request: Value) {
+        self.events.insert(
+            0,
+            json!({
+                "id": instance_id(),
+                "type": event_type,
+                "status": status,
+                "source": "user",
+                "timestamp": now_millis(),
+                "request": request,
+            }),
+        );
+    }
+}
+
+impl FakeLease {
+    fn to_json(&self) -> Value {
+        json!({
+            "status": "success",
+            "data": {
+                "nonce": self.nonce,
+                "owner": self.owner,
+                "description": self.description,
+                "expires_at": self.expires_at,
+                "version": self.version,
+            },
+        })
+    }
+}
+
+impl FakeMachine {
+    fn active_lease(&self) -> Option<&FakeLease> {
+        self.lease
+            .as_ref()
+            .filter(|lease| lease.expires_at > now_millis() / 1000)
+    }
+}
+
+/// Rejects a mutating call that doesn't carry the nonce of the machine's active lease.
+fn lease_conflict(machine: &FakeMachine, headers: &HeaderMap) -> Option<Response> {
+    let lease = machine.active_lease()?;
+    let nonce = headers
+        .get(LEASE_NONCE_HEADER)
+        .and_then(|value| value.to_str().ok());
+    (nonce != Some(lease.nonce.as_str())).then(|| {
+        error(
+            StatusCode::CONFLICT,
+            &format!("machine is leased by {}", lease.owner),
+        )
+    })
+}
+
+impl FakeVolume {
+    fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "name": self.name,
+            "region": self.region,
+            "size_gb": self.size_gb,
+            "encrypted": self.encrypted,
+            "fstype": self.fstype,
+            "state": self.state,
+            "auto_backup_enabled": self.auto_backup_enabled,
+            "snapshot_retention": self.snapshot_retention,
+            "attached_machine_id": self.attached_machine_id,
+            "attached_alloc_id": Value::Null,
+            "block_size": 4096,
+            "blocks": self.size_gb * 262_144,
+            "blocks_avail": self.size_gb * 262_144,
+            "blocks_free": self.size_gb * 262_144,
+            "created_at": self.created_at,
+            "host_status": "ok",
+            "zone": "f4a1",
+        })
+    }
+}
+
+/// Moves a machine to `via` now and to `to` after the transition delay.
+///
+/// The second step is skipped if something else changed the machine in the meantime.
+fn transition(shared: &Arc<Shared>, app_name: &str, machine_id: &str, via: &str, to: &str) {
+    let delay = {
+        let mut state = shared.lock();
+        let delay = state.transition_delay;
+        let Some(machine) = state
+            .apps
+            .get_mut(app_name)
+            .and_then(|app| app.machines.get_mut(machine_id))
+        else {
+            return;
+        };
+        machine.state = via.to_string();
+        machine.updated_at = now_rfc3339();
+        delay
+    };
+    shared.notify();
+
+    let shared = shared.clone();
+    let (app_name, machine_id, via, to) = (
+        app_name.to_string(),
+        machine_id.to_string(),
+        via.to_string(),
+        to.to_string(),
+    );
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        {
+            let mut state = shared.lock();
+            let boots = state.boots(&app_name, &machine_id);
+            let Some(machine) = state
+                .apps
+                .get_mut(&app_name)
+                .and_then(|app| app.machines.get_mut(&machine_id))
+            else {
+                return;
+            };
+            if machine.state != via {
+                return;
+            }
+            let to = if to == "started" && !boots {
+                "failed".to_string()
+            } else {
+                to
+            };
+            machine.state = to.clone();
+            machine.updated_at = now_rfc3339();
+            match to.as_str() {
+                "started" => machine.record_event("start", "started", Value::Null),
+                "stopped" => machine.record_event(
+                    "exit",
+                    "stopped",
+                    json!({ "exit_event": { "exit_code": 0, "requested_stop": true } }),
+                ),
+                "suspended" => machine.record_event("suspend", "suspended", Value::Null),
+                "destroyed" => machine.record_event("destroy", "destroyed", Value::Null),
+                "failed" => machine.record_event("start", "failed", Value::Null),
+                _ => {}
+            }
+            if to == "destroyed" {
+                if let Some(app) = state.apps.get_mut(&app_name) {
+                    for volume in app.volumes.values_mut() {
+                        if volume.attached_machine_id.as_deref() == Some(machine_id.as_str()) {
+                            volume.attached_machine_id = None;
+                        }
+                    }
+                }
+            }
+        }
+        shared.notify();
+    });
+}
+
+// apps
+
+async fn create_app(State(shared): SharedState, Json(body): Json<Value>) -> Response {
+    let (Some(app_name), Some(org_slug)) = (
+        body.get("app_name").and_then(Value::as_str),
+        body.get("org_slug").and_then(Value::as_str),
+    ) else {
+        return error(
+            StatusCode::BAD_REQUEST,
+            "app_name and org_slug are required",
+        );
+    };
+
+    let mut state = shared.lock();
+    if state.apps.contains_key(app_name) {
+        return error(StatusCode::CONFLICT, "app name is already taken");
+    }
+    let id = random_id(14);
+    state.apps.insert(
+        app_name.to_string(),
+        FakeApp {
+            id: id.clone(),
+            org_slug: org_slug.to_string(),
+            machines: BTreeMap::new(),
+            volumes: BTreeMap::new(),
+            secrets: BTreeMap::new(),
+        },
+    );
+
+    (
+        StatusCode::CREATED,
+        Json(json!({ "id": id, "created_at": now_millis() })),
+    )
+        .into_response()
+}
+
+async fn list_apps(State(shared): SharedState, Query(params): Params) -> Response {
+    let state = shared.lock();
+    let org_slug = params.get("org_slug");
+    let apps: Vec<Value> = state
+        .apps
+        .iter()
+        .filter(|(_, app)| org_slug.is_none_or(|slug| &app.org_slug == slug))
+        .map(|(name, app)| {
+            json!({
+                "id": app.id,
+                "name": name,
+                "machine_count": app.machines.values().filter(|m| m.state != "destroyed").count(),
+                "network": "default",
+            })
+        })
+        .collect();
+
+    Json(json!({ "total_apps": apps.len(), "apps": apps })).into_response()
+}
+
+async fn delete_app(State(shared): SharedState, Path(app_name): Path<String>) -> Response {
+    match shared.lock().apps.remove(&app_name) {
+        Some(_) => StatusCode::ACCEPTED.into_response(),
+        None => app_not_found(),
+    }
+}
+
+// machines
+
+async fn create_machine(
+    State(shared): SharedState,
+    Path(app_name): Path<String>,
+    Json(body): Json<Value>,
+) -> Response {
+    let Some(config) = body.get("config").filter(|config| config.is_object()) else {
+        return error(StatusCode::BAD_REQUEST, "config is required");
+    };
+    let skip_launch = body.get("skip_launch").and_then(Value::as_bool) == Some(true);
+
+    let response = {
+        let mut state = shared.lock();
+        if !state.apps.contains_key(&app_name) {
+            return app_not_found();
+        }
+        if state.creates_before_failures > 0 {
+            state.creates_before_failures -= 1;
+        } else if state.failing_creates > 0 {
+            state.failing_creates -= 1;
+            return error(
+                StatusCode::PRECONDITION_FAILED,
+                "insufficient resources available to fulfill request",
+            );
+        }
+        let unhealthy = state.is_unhealthy(config);
+        let Some(app) = state.apps.get_mut(&app_name) else {
+            return app_not_found();
+        };
+
+        let id = random_id(14);
+        let name = body
+            .get("name")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("machine-{}", &id[..6]));
+        if app
+            .machines
+            .values()
+            .any(|machine| machine.name == name && machine.state != "destroyed")
+        {
+            return error(
+                StatusCode::CONFLICT,
+                "a machine with this name already exists",
+            );
+        }
+
+        let region = body
+            .get("region")
+            .and_then(Value::as_str)
+            .unwrap_or("iad")
+            .to_string();
+        for mount in config
+            .get("mounts")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let volume_id = mount
+                .get("volume")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            match app.volumes.get_mut(volume_id) {
+                Some(volume) if volume.region != region => {
+                    return error(
+                        StatusCode::PRECONDITION_FAILED,
+                        &format!("volume {} is in region {}", volume_id, volume.region),
+                    )
+                }
+                Some(volume) if volume.attached_machine_id.is_none() => {
+                    volume.attached_machine_id = Some(id.clone());
+                }
+                Some(_) => {
+                    return error(
+                        StatusCode::CONFLICT,
+                        "volume is already attached to a machine",
+                    )
+                }
+                None => return error(StatusCode::NOT_FOUND, "volume not found"),
+            }
+        }
+
+        let now = now_rfc3339();
+        let mut machine = FakeMachine {
+            id: id.clone(),
+            name,
+            region,
+            instance_id: instance_id(),
+            state: "created".to_string(),
+            config: config.clone(),
+            private_ip: format!(
+                "fdaa:0:1:a7b:{:x}:{:x}:{:x}:2",
+                fastrand::u16(..),
+                fastrand::u16(..),
+                fastrand::u16(..)
+            ),
+            created_at: now.clone(),
+            updated_at: now,
+            events: Vec::new(),
+            lease: None,
+            cordoned: false,
+            busy: false,
+            cpu: 0,
+            unhealthy,
+        };
+        machine.record_event("launch", "created", Value::Null);
+        let response = machine.to_json();
+        app.machines.insert(id.clone(), machine);
+        (id, response)
+    };
+    shared.notify();
+
+    let (id, response) = response;
+    if !skip_launch {
+        transition(&shared, &app_name, &id, "starting", "started");
+    }
+    Json(response).into_response()
+}
+
+async fn list_machines(
+    State(shared): SharedState,
+    Path(app_name): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let state = shared.lock();
+    let Some(app) = state.apps.get(&app_name) else {
+        return app_not_found();
+    };
+    let flag = |name: &str| query.get(name).is_some_and(|value| value == "true");
+    let states: Option<Vec<&str>> = query.get("state").map(|states| states.split(',').collect());
+    let metadata: Vec<(&str, &str)> = query
+        .iter()
+        .filter_map(|(name, value)| Some((name.strip_prefix("metadata.")?, value.as_str())))
+        .collect();
+
+    let machines: Vec<Value> = app
+        .machines
+        .values()
+        .filter(|machine| flag("include_deleted") || machine.state != "destroyed")
+        .filter(|machine| {
+            query
+                .get("region")
+                .is_none_or(|region| *region == machine.region)
+        })
+        .filter(|machine| {
+            states
+                .as_ref()
+                .is_none_or(|states| states.contains(&machine.state.as_str()))
+        })
+        .filter(|machine| {
+            metadata
+                .iter()
+                .all(|(key, value)| machine.config["metadata"][*key].as_str() == Some(*value))
+        })
+        .map(|machine| {
+            let mut json = machine.to_json();
+            if flag("summary") {
+                if let Some(json) = json.as_object_mut() {
+                    json.remove("config");
+                    json.remove("events");
+                }
+            }
+            json
+        })
+        .collect();
+    Json(machines).into_response()
+}
+
+async fn get_machine(
+    State(shared): SharedState,
+    Path((app_name, machine_id)): Path<(String, String)>,
+) -> Response {
+    let state = shared.lock();
+    let Some(app) = state.apps.get(&app_name) else {
+        return app_not_found();
+    };
+    match app.machines.get(&machine_id) {
+        Some(machine) => Json(machine.to_json()).into_response(),
+        None => machine_not_found(),
+    }
+}
+
+async fn update_machine(
+    State(shared): SharedState,
+    Path((app_name, machine_id)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    let Some(config) = body.get("config").filter(|config| config.is_object()) else {
+        return error(StatusCode::BAD_REQUEST, "config is required");
+    };
+    let skip_launch = body.get("skip_launch").and_then(Value::as_bool) == Some(true);
+
+    let response = {
+        let mut state = shared.lock();
+        let unhealthy = state.is_unhealthy(config);
+        let Some(app) = state.apps.get_mut(&app_name) else {
+            return app_not_found();
+        };
+        let Some(machine) = app.machines.get_mut(&machine_id) else {
+            return machine_not_found();
+        };
+        if let Some(conflict) = lease_conflict(machine, &headers) {
+            return conflict;
+        }
+        if machine.state == "destroyed" || machine.state == "destroying" {
+            return error(StatusCode::PRECONDITION_FAILED, "machine is destroyed");
+        }
+        if let Some(version) = body.get("current_version").and_then(Value::as_str) {
+            if version != machine.instance_id {
+                return error(
+                    StatusCode::PRECONDITION_FAILED,
+                    &format!(
+                        "machine version mismatch: expected {}, current is {}",
+                        version, machine.instance_id
+                    ),
+                );
+            }
+        }
+
+        machine.config = config.clone();
+        machine.unhealthy = unhealthy;
+        if let Some(name) = body.get("name").and_then(Value::as_str) {
+            machine.name = name.to_string();
+        }
+        machine.instance_id = instance_id();
+        machine.updated_at = now_rfc3339();
+        machine.record_event("update", "replacing", Value::Null);
+        machine.to_json()
+    };
+
+    let target = if skip_launch { "stopped" } else { "started" };
+    transition(&shared, &app_name, &machine_id, "replacing", target);
+    Json(response).into_response()
+}
+
+async fn start_machine(
+    State(shared): SharedState,
+    Path((app_name, machine_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    let previous_state = {
+        let state = shared.lock();
+        let Some(app) = state.apps.get(&app_name) else {
+            return app_not_found();
+        };
+        let Some(machine) = app.machines.get(&machine_id) else {
+            return machine_not_found();
+        };
+        if let Some(conflict) = lease_conflict(machine, &headers) {
+            return conflict;
+        }
+        machine.state.clone()
+    };
+
+    match previous_state.as_str() {
+        "created" | "stopped" | "suspended" => {
+            transition(&shared, &app_name, &machine_id, "starting", "started");
+        }
+        "started" | "starting" => {}
+        _ => {
+            return error(
+                StatusCode::PRECONDITION_FAILED,
+                &format!("machine cannot be started from state {}", previous_state),
+            )
+        }
+    }
+
+    Json(json!({
+        "previous_state": previous_state,
+        "migrated": false,
+        "new_host": "",
+    }))
+    .into_response()
+}
+
+async fn stop_machine(
+    State(shared): SharedState,
+    Path((app_name, machine_id)): Path<(String, String)>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let options: Value = if body.is_empty() {
+        Value::Null
+    } else {
+        match serde_json::from_slice(&body) {
+            Ok(options) => options,
+            Err(err) => return error(StatusCode::BAD_REQUEST, &err.to_string()),
+        }
+    };
+    if let Some(signal) = options.get("signal") {
+        if serde_json::from_value::<Signal>(signal.clone()).is_err() {
+            return error(StatusCode::BAD_REQUEST, "invalid signal");
+        }
+    }
+
+    let current_state = {
+        let mut state = shared.lock();
+        let Some(app) = state.apps.get_mut(&app_name) else {
+            return app_not_found();
+        };
+        let Some(machine) = app.machines.get_mut(&machine_id) else {
+            return machine_not_found();
+        };
+        if let Some(conflict) = lease_conflict(machine, &headers) {
+            return conflict;
+        }
+        if matches!(machine.state.as_str(), "started" | "starting" | "replacing") {
+            machine.record_event("stop", "stopping", options);
+        }
+        machine.state.clone()
+    };
+
+    match current_state.as_str() {
+        "started" | "starting" | "replacing" => {
+            transition(&shared, &app_name, &machine_id, "stopping", "stopped");
+            ok()
+        }
+        "stopped" | "stopping" | "created" | "suspended" => ok(),
+        _ => error(
+            StatusCode::PRECONDITION_FAILED,
+            &format!("machine cannot be stopped from state {}", current_state),
+        ),
+    }
+}
+
+async fn signal_machine(
+    State(shared): SharedState,
+    Path((app_name, machine_id)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    let Some(Ok(signal)) = body
+        .get("signal")
+        .map(|signal| serde_json::from_value::<Signal>(signal.clone()))
+    else {
+        return error(StatusCode::BAD_REQUEST, "invalid signal");
+    };
+
+    let delivered = {
+        let mut state = shared.lock();
+        let Some(app) = state.apps.get_mut(&app_name) else {
+            return app_not_found();
+        };
+        let Some(machine) = app.machines.get_mut(&machine_id) else {
+            return machine_not_found();
+        };
+        if let Some(conflict) = lease_conflict(machine, &headers) {
+            return conflict;
+        }
+        if machine.state != "started" {
+            return error(StatusCode::PRECONDITION_FAILED, "machine is not started");
+        }
+        let status = machine.state.clone();
+        machine.record_event("signal", &status, json!({ "signal": signal }));
+        signal
+    };
+
+    // like the real API, a fatal signal takes the machine down
+    if delivered == Signal::SIGKILL {
+        transition(&shared, &app_name, &machine_id, "stopping", "stopped");
+    }
+    ok()
+}
+
+async fn suspend_machine(
+    State(shared): SharedState,
+    Path((app_name, machine_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    let current_state = {
+        let state = shared.lock();
+        let Some(app) = state.apps.get(&app_name) else {
+            return app_not_found();
+        };
+        let Some(machine) = app.machines.get(&machine_id) else {
+            return machine_not_found();
+        };
+        if let Some(conflict) = lease_conflict(machine, &headers) {
+            return conflict;
+        }
+        machine.state.clone()
+    };
+
+    match current_state.as_str() {
+        "started" => {
+            transition(&shared, &app_name, &machine_id, "suspending", "suspended");
+            ok()
+        }
+        "suspended" | "suspending" => ok(),
+        _ => error(
+            StatusCode::PRECONDITION_FAILED,
+            &format!("machine cannot be suspended from state {}", current_state),
+        ),
+    }
+}
+
+async fn restart_machine(
+    State(shared): SharedState,
+    Path((app_name, machine_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    {
+        let mut state = shared.lock();
+        let Some(app) = state.apps.get_mut(&app_name) else {
+            return app_not_found();
+        };
+        let Some(machine) = app.machines.get_mut(&machine_id) else {
+            return machine_not_found();
+        };
+        if let Some(conflict) = lease_conflict(machine, &headers) {
+            return conflict;
+        }
+        if machine.state != "started" {
+            return error(StatusCode::PRECONDITION_FAILED, "machine is not started");
+        }
+        machine.record_event("restart", "restarting", Value::Null);
+    }
+
+    transition(&shared, &app_name, &machine_id, "starting", "started");
+    ok()
+}
+
+async fn delete_machine(
+    State(shared): SharedState,
+    Path((app_name, machine_id)): Path<(String, String)>,
+    headers: HeaderMap,
+    Query(params): Params,
+) -> Response {
+    let force = params.get("force").map(String::as_str) == Some("true");
+    let current_state = {
+        let state = shared.lock();
+        let Some(app) = state.apps.get(&app_name) else {
+            return app_not_found();
+        };
+        let Some(machine) = app.machines.get(&machine_id) else {
+            return machine_not_found();
+        };
+        if let Some(conflict) = lease_conflict(machine, &headers) {
+            return conflict;
+        }
+        machine.state.clone()
+    };
+
+    match current_state.as_str() {
+        "destroyed" | "destroying" => ok(),
+        "started" | "starting" | "replacing" | "stopping" if !force => error(
+            StatusCode::PRECONDITION_FAILED,
+            "unable to destroy machine, not currently stopped",
+        ),
+        _ => {
+            transition(&shared, &app_name, &machine_id, "destroying", "destroyed");
+            ok()
+        }
+    }
+}
+
+async fn wait_machine(
+    State(shared): SharedState,
+    Path((app_name, machine_id)): Path<(String, String)>,
+    Query(params): Params,
+) -> Response {
+    let target = params
+        .get("state")
+        .cloned()
+        .unwrap_or_else(|| "started".to_string());
+    if !["started", "stopped", "suspended", "destroyed"].contains(&target.as_str()) {
+        return error(StatusCode::BAD_REQUEST, "invalid state");
+    }
+    let timeout = match params.get("timeout").map(|timeout| timeout.parse::<u64>()) {
+        None => MAX_WAIT_SECONDS,
+        Some(Ok(timeout)) if timeout <= MAX_WAIT_SECONDS => timeout,
+        Some(_) => return error(StatusCode::BAD_REQUEST, "invalid timeout"),
+    };
+    let instance_id = params.get("instance_id");
+
+    let mut changes = shared.changes.subscribe();
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(timeout);
+    loop {
+        {
+            let state = shared.lock();
+            let Some(app) = state.apps.get(&app_name) else {
+                return app_not_found();
+            };
+            let Some(machine) = app.machines.get(&machine_id) else {
+                return machine_not_found();
+            };
+            let same_instance = instance_id.is_none_or(|id| id == &machine.instance_id);
+            if machine.state == target && same_instance {
+                return ok();
+            }
+        }
+
+        match tokio::time::timeout_at(deadline, changes.changed()).await {
+            Ok(Ok(())) => continue,
+            _ => return error(StatusCode::REQUEST_TIMEOUT, "deadline_exceeded"),
+        }
+    }
+}
+
+async fn list_events(
+    State(shared): SharedState,
+    Path((app_name, machine_id)): Path<(String, String)>,
+) -> Response {
+    let state = shared.lock();
+    let Some(app) = state.apps.get(&app_name) else {
+        return app_not_found();
+    };
+    match app.machines.get(&machine_id) {
+        Some(machine) => Json(&machine.events).into_response(),
+        None => machine_not_found(),
+    }
+}
+
+async fn list_processes(
+    State(shared): SharedState,
+    Path((app_name, machine_id)): Path<(String, String)>,
+) -> Response {
+    let mut state = shared.lock();
+    let Some(app) = state.apps.get_mut(&app_name) else {
+        return app_not_found();
+    };
+    let Some(machine) = app.machines.get_mut(&machine_id) else {
+        return machine_not_found();
+    };
+    if machine.state != "started" {
+        return error(StatusCode::PRECONDITION_FAILED, "machine is not started");
+    }
+    if machine.busy {
+        machine.cpu += 10;
+    }
+
+    Json(json!([{
+        "pid": 1,
+        "command": "/.fly/init",
+        "directory": "/",
+        "cpu": machine.cpu,
+        "rss": 1_048_576,
+        "rtime": 0,
+        "stime": 0,
+        "listen_sockets": [],
+    }]))
+    .into_response()
+}
+
+async fn get_metadata(
+    State(shared): SharedState,
+    Path((app_name, machine_id)): Path<(String, String)>,
+) -> Response {
+    let state = shared.lock();
+    let Some(app) = state.apps.get(&app_name) else {
+        return app_not_found();
+    };
+    let Some(machine) = app.machines.get(&machine_id) else {
+        return machine_not_found();
+    };
+    let metadata = machine
+        .config
+        .get("metadata")
+        .filter(|metadata| metadata.is_object())
+        .cloned()
+        .unwrap_or_else(|| json!({}));
+    Json(metadata).into_response()
+}
+
+async fn set_metadata_key(
+    State(shared): SharedState,
+    Path((app_name, machine_id, key)): Path<(String, String, String)>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    let Some(value) = body.get("value").and_then(Value::as_str) else {
+        return error(StatusCode::BAD_REQUEST, "value is required");
+    };
+    let value = value.to_string();
+    edit_metadata(&shared, &app_name, &machine_id, &headers, |metadata| {
+        metadata.insert(key, Value::String(value));
+    })
+}
+
+async fn delete_metadata_key(
+    State(shared): SharedState,
+    Path((app_name, machine_id, key)): Path<(String, String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    edit_metadata(&shared, &app_name, &machine_id, &headers, |metadata| {
+        metadata.remove(&key);
+    })
+}
+
+/// Applies `edit` to the machine's metadata in place, leaving its state and instance alone.
+fn edit_metadata(
+    shared: &Shared,
+    app_name: &str,
+    machine_id: &str,
+    headers: &HeaderMap,
+    edit: impl FnOnce(&mut serde_json::Map<String, Value>),
+) -> Response {
+    let mut state = shared.lock();
+    let Some(app) = state.apps.get_mut(app_name) else {
+        return app_not_found();
+    };
+    let Some(machine) = app.machines.get_mut(machine_id) else {
+        return machine_not_found();
+    };
+    if let Some(conflict) = lease_conflict(machine, headers) {
+        return conflict;
+    }
+
+    let Some(config) = machine.config.as_object_mut() else {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, "machine has no config");
+    };
+    let metadata = config
+        .entry("metadata")
+        .and_modify(|metadata| {
+            if !metadata.is_object() {
+                *metadata = json!({});
+            }
+        })
+        .or_insert_with(|| json!({}));
+    if let Some(metadata) = metadata.as_object_mut() {
+        edit(metadata);
+    }
+    machine.updated_at = now_rfc3339();
+    StatusCode::NO_CONTENT.into_response()
+}
+
+async fn cordon_machine(
+    State(shared): SharedState,
+    Path((app_name, machine_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    set_cordoned(&shared, &app_name, &machine_id, &headers, true)
+}
+
+async fn uncordon_machine(
+    State(shared): SharedState,
+    Path((app_name, machine_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    set_cordoned(&shared, &app_name, &machine_id, &headers, false)
+}
+
+fn set_cordoned(
+    shared: &Shared,
+    app_name: &str,
+    machine_id: &str,
+    headers: &HeaderMap,
+    cordoned: bool,
+) -> Response {
+    let mut state = shared.lock();
+    let Some(app) = state.apps.get_mut(app_name) else {
+        return app_not_found();
+    };
+    let Some(machine) = app.machines.get_mut(machine_id) else {
+        return machine_not_found();
+    };
+    if let Some(conflict) = lease_conflict(machine, headers) {
+        return conflict;
+    }
+    machine.cordoned = cordoned;
+    ok()
+}
+
+async fn exec_machine(
+    State(shared): SharedState,
+    Path((app_name, machine_id)): Path<(String, String)>,
+    Json(body): Json<Value>,
+) -> Response {
+    let command: Vec<String> = body
+        .get("command")
+        .and_then(Value::as_array)
+        .map(|command| {
+            command
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    if command.is_empty() {
+        return error(StatusCode::BAD_REQUEST, "command is required");
+    }
+
+    let handler = {
+        let state = shared.lock();
+        let Some(app) = state.apps.get(&app_name) else {
+            return app_not_found();
+        };
+        let Some(machine) = app.machines.get(&machine_id) else {
+            return machine_not_found();
+        };
+        if machine.state != "started" {
+            return error(StatusCode::PRECONDITION_FAILED, "machine is not started");
+        }
+        state.exec_handler.clone()
+    };
+
+    let reply = handler(&command, body.get("stdin").and_then(Value::as_str));
+    Json(json!({
+        "exit_code": reply.exit_code,
+        "exit_signal": reply.exit_signal,
+        "stdout": reply.stdout,
+        "stderr": reply.stderr,
+    }))
+    .into_response()
+}
+
+async fn get_lease(
+    State(shared): SharedState,
+    Path((app_name, machine_id)): Path<(String, String)>,
+) -> Response {
+    let state = shared.lock();
+    let Some(app) = state.apps.get(&app_name) else {
+        return app_not_found();
+    };
+    let Some(machine) = app.machines.get(&machine_id) else {
+        return machine_not_found();
+    };
+    match machine.active_lease() {
+        Some(lease) => Json(lease.to_json()).into_response(),
+        None => error(StatusCode::NOT_FOUND, "lease not found"),
+    }
+}
+
+async fn create_lease(
+    State(shared): SharedState,
+    Path((app_name, machine_id)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    let ttl = body.get("ttl").and_then(Value::as_u64).unwrap_or(30);
+    let mut state = shared.lock();
+    let Some(app) = state.apps.get_mut(&app_name) else {
+        return app_not_found();
+    };
+    let Some(machine) = app.machines.get_mut(&machine_id) else {
+        return machine_not_found();
+    };
+    if let Some(conflict) = lease_conflict(machine, &headers) {
+        return conflict;
+    }
+
+    let expires_at = now_millis() / 1000 + ttl;
+    let lease = match machine
+        .lease
+        .as_mut()
+        .filter(|lease| lease.expires_at > now_millis() / 1000)
+    {
+        // the caller holds the active lease, so this is a refresh
+        Some(lease) => {
+            lease.expires_at = expires_at;
+            lease
+        }
+        None => machine.lease.insert(FakeLease {
+            nonce: random_id(12),
+            owner: "fake@fly.io".to_string(),
+            description: body
+                .get("description")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            expires_at,
+            version: instance_id(),
+        }),
+    };
+    Json(lease.to_json()).into_response()
+}
+
+async fn release_lease(
+    State(shared): SharedState,
+    Path((app_name, machine_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    let mut state = shared.lock();
+    let Some(app) = state.apps.get_mut(&app_name) else {
+        return app_not_found();
+    };
+    let Some(machine) = app.machines.get_mut(&machine_id) else {
+        return machine_not_found();
+    };
+    if let Some(conflict) = lease_conflict(machine, &headers) {
+        return conflict;
+    }
+    machine.lease = None;
+    ok()
+}
+
+// volumes
+
+async fn create_volume(
+    State(shared): SharedState,
+    Path(app_name): Path<String>,
+    Json(body): Json<Value>,
+) -> Response {
+    let (Some(name), Some(size_gb)) = (
+        body.get("name").and_then(Value::as_str),
+        body.get("size_gb").and_then(Value::as_u64),
+    ) else {
+        return error(StatusCode::BAD_REQUEST, "name and size_gb are required");
+    };
+
+    let mut state = shared.lock();
+    let Some(app) = state.apps.get_mut(&app_name) else {
+        return app_not_found();
+    };
+    let volume = FakeVolume {
+        id: format!("vol_{}", random_id(16)),
+        name: name.to_string(),
+        region: body
+            .get("region")
+            .and_then(Value::as_str)
+            .unwrap_or("iad")
+            .to_string(),
+        size_gb,
+        encrypted: body
+            .get("encrypted")
+            .and_then(Value::as_bool)
+            .unwrap_or(true),
+        fstype: body
+            .get("fstype")
+            .and_then(Value::as_str)
+            .unwrap_or("ext4")
+            .to_string(),
+        state: "created".to_string(),
+        auto_backup_enabled: true,
+        snapshot_retention: body
+            .get("snapshot_retention")
+            .and_then(Value::as_u64)
+            .unwrap_or(5),
+        attached_machine_id: None,
+        created_at: now_rfc3339(),
+        snapshots: Vec::new(),
+    };
+    let response = volume.to_json();
+    app.volumes.insert(volume.id.clone(), volume);
+    Json(response).into_response()
+}
+
+async fn list_volumes(State(shared): SharedState, Path(app_name): Path<String>) -> Response {
+    let state = shared.lock();
+    let Some(app) = state.apps.get(&app_name) else {
+        return app_not_found();
+    };
+    let volumes: Vec<Value> = app.volumes.values().map(FakeVolume::to_json).collect();
+    Json(volumes).into_response()
+}
+
+fn with_volume(
+    shared: &Shared,
+    app_name: &str,
+    volume_id: &str,
+    f: impl FnOnce(&mut FakeVolume) -> Response,
+) -> Response {
+    let mut state = shared.lock();
+    let Some(app) = state.apps.get_mut(app_name) else {
+        return app_not_found();
+    };
+    match app.volumes.get_mut(volume_id) {
+        Some(volume) => f(volume),
+        None => error(StatusCode::NOT_FOUND, "volume not found"),
+    }
+}
+
+async fn get_volume(
+    State(shared): SharedState,
+    Path((app_name, volume_id)): Path<(String, String)>,
+) -> Response {
+    with_volume(&shared, &app_name, &volume_id, |volume| {
+        Json(volume.to_json()).into_response()
+    })
+}
+
+async fn update_volume(
+    State(shared): SharedState,
+    Path((app_name, volume_id)): Path<(String, String)>,
+    Json(body): Json<Value>,
+) -> Response {
+    with_volume(&shared, &app_name, &volume_id, |volume| {
+        if let Some(enabled) = body.get("auto_backup_enabled").and_then(Value::as_bool) {
+            volume.auto_backup_enabled = enabled;
+        }
+        if let Some(retention) = body.get("snapshot_retention").and_then(Value::as_u64) {
+            volume.snapshot_retention = retention;
+        }
+        Json(volume.to_json()).into_response()
+    })
+}
+
+async fn extend_volume(
+    State(shared): SharedState,
+    Path((app_name, volume_id)): Path<(String, String)>,
+    Json(body): Json<Value>,
+) -> Response {
+    with_volume(&shared, &app_name, &volume_id, |volume| {
+        match body.get("size_gb").and_then(Value::as_u64) {
+            Some(size_gb) if size_gb > volume.size_gb => {
+                volume.size_gb = size_gb;
+                Json(volume.to_json()).into_response()
+            }
+            _ => error(
+                StatusCode::BAD_REQUEST,
+                "size_gb must be larger than the current size",
+            ),
+        }
+    })
+}
+
+async fn destroy_volume(
+    State(shared): SharedState,
+    Path((app_name, volume_id)): Path<(String, String)>,
+) -> Response {
+    let mut state = shared.lock();
+    let Some(app) = state.apps.get_mut(&app_name) else {
+        return app_not_found();
+    };
+    match app.volumes.get(&volume_id) {
+        Some(volume) if volume.attached_machine_id.is_some() => error(
+            StatusCode::PRECONDITION_FAILED,
+            "volume is attached to a machine",
+        ),
+        Some(_) => {
+            let mut volume = app.volumes.remove(&volume_id).expect("volume exists");
+            volume.state = "destroyed".to_string();
+            Json(volume.to_json()).into_response()
+        }
+        None => error(StatusCode::NOT_FOUND, "volume not found"),
+    }
+}
+
+async fn list_snapshots(
+    State(shared): SharedState,
+    Path((app_name, volume_id)): Path<(String, String)>,
+) -> Response {
+    with_volume(&shared, &app_name, &volume_id, |volume| {
+        Json(&volume.snapshots).into_response()
+    })
+}
+
+async fn create_snapshot(
+    State(shared): SharedState,
+    Path((app_name, volume_id)): Path<(String, String)>,
+) -> Response {
+    with_volume(&shared, &app_name, &volume_id, |volume| {
+        volume.snapshots.push(json!({
+            "id": format!("vs_{}", random_id(16)),
+            "created_at": now_rfc3339(),
+            "digest": random_id(64),
+            "retention_days": volume.snapshot_retention,
+            "size": volume.size_gb * 1_073_741_824,
+            "status": "created",
+        }));
+        ok()
+    })
+}
+
+// secrets
+
+fn secret_json(secret: &FakeSecret) -> Value {
+    json!({
+        "label": secret.label,
+        "publickey": [],
+        "type": secret.secret_type,
+    })
+}
+
+async fn list_secrets(State(shared): SharedState, Path(app_name): Path<String>) -> Response {
+    let state = shared.lock();
+    let Some(app) = state.apps.get(&app_name) else {
+        return app_not_found();
+    };
+    let secrets: Vec<Value> = app.secrets.values().map(secret_json).collect();
+    Json(secrets).into_response()
+}
+
+fn store_secret(shared: &Shared, app_name: &str, label: String, secret_type: String) -> Response {
+    let mut state = shared.lock();
+    let Some(app) = state.apps.get_mut(app_name) else {
+        return app_not_found();
+    };
+    let secret = FakeSecret { label, secret_type };
+    let response = secret_json(&secret);
+    app.secrets.insert(secret.label.clone(), secret);
+    Json(response).into_response()
+}
+
+async fn create_secret(
+    State(shared): SharedState,
+    Path((app_name, label, secret_type)): Path<(String, String, String)>,
+    Json(body): Json<Value>,
+) -> Response {
+    if !body.get("value").is_some_and(Value::is_array) {
+        return error(StatusCode::BAD_REQUEST, "value is required");
+    }
+    store_secret(&shared, &app_name, label, secret_type)
+}
+
+async fn generate_secret(
+    State(shared): SharedState,
+    Path((app_name, label, secret_type)): Path<(String, String, String)>,
+) -> Response {
+    store_secret(&shared, &app_name, label, secret_type)
+}
+
+async fn destroy_secret(
+    State(shared): SharedState,
+    Path((app_name, label)): Path<(String, String)>,
+) -> Response {
+    let mut state = shared.lock();
+    let Some(app) = state.apps.get_mut(&app_name) else {
+        return app_not_found();
+    };
+    match app.secrets.remove(&label) {
+        Some(_) => ok(),
+        None => error(StatusCode::NOT_FOUND, "secret not found"),
+    }
+}
@@ -0,0 +1,39 @@
+//! Test helpers, enabled with the `testing` feature.
+//!
+//! [`FakeFly`] is an in-memory stand-in for the Machines API that runs on localhost, so
+//! code using the SDK can be tested without a Fly account or real infrastructure:
+//!
+//! ```rust
+//! use fly_sdk::machines::{MachineConfig, MachineRequest, MachineState};
+//! use fly_sdk::testing::FakeFly;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), fly_sdk::FlyError> {
+//! let fake = FakeFly::start().await;
+//! let fly = fake.client();
+//!
+//! fly.apps.create("my-app", "personal").await?;
+//! let machine = fly
+//!     .machines
+//!     .create(
+//!         "my-app",
+//!         MachineRequest::new(MachineConfig::builder().image("nginx").build(), None, None),
+//!     )
+//!     .await?;
+//! let id = machine.id.unwrap();
+//! fly.machines
+//!     .wait_for_machine_state("my-app", &id, MachineState::Started, None, None)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Cassette`] records real exchanges with the Machines API to a JSON file, with
+//! credentials redacted, and replays them later without network access. Replayed
+//! cassettes make good regression fixtures for the SDK's response types.
+
+mod cassette;
+mod fake_fly;
+
+pub use cassette::{Cassette, CassetteServer, Interaction, RecordedRequest, RecordedResponse};
+pub use fake_fly::{ExecReply, FakeFly, FAKE_TOKEN};
@@ -0,0 +1,384 @@
+//! Sources for the API token sent with every request.
+//!
+//! The token is looked up for each request, so a provider can return a different value
+//! at any time. [`RefreshableToken`] is the simplest way to rotate tokens in a
+//! long-running process:
+//!
+//! ```rust
+//! use fly_sdk::token::RefreshableToken;
+//! use fly_sdk::FlyControl;
+//!
+//! # fn main() -> Result<(), fly_sdk::FlyError> {
+//! let token = RefreshableToken::new("first_deploy_token");
+//! let fly = FlyControl::builder().token_provider(token.clone()).build()?;
+//!
+//! // later, every manager of `fly` (and its clones) picks up the new token
+//! token.set("rotated_deploy_token");
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::FlyError;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+
+/// Supplies the API token for each request.
+///
+/// Closures returning `Result<String, FlyError>` implement this trait as well.
+pub trait TokenProvider: Send + Sync {
+    fn token(&self) -> Result<String, FlyError>;
+}
+
+impl<F> TokenProvider for F
+where
+    F: Fn() -> Result<String, FlyError> + Send + Sync,
+{
+    fn token(&self) -> Result<String, FlyError> {
+        self()
+    }
+}
+
+/// A fixed token.
+#[derive(Clone)]
+pub struct StaticToken(String);
+
+impl StaticToken {
+    pub fn new(token: &str) -> Self {
+        Self(token.to_string())
+    }
+}
+
+impl TokenProvider for StaticToken {
+    fn token(&self) -> Result<String, FlyError> {
+        Ok(self.0.clone())
+    }
+}
+
+impl std::fmt::Debug for StaticToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("StaticToken(..)")
+    }
+}
+
+/// Reads the token from the first set environment variable, `FLY_API_TOKEN` and then
+/// `FLY_ACCESS_TOKEN` by default.
+#[derive(Debug, Clone)]
+pub struct EnvToken {
+    vars: Vec<String>,
+}
+
+impl Default for EnvToken {
+    fn default() -> Self {
+        Self::new(&["FLY_API_TOKEN", "FLY_ACCESS_TOKEN"])
+    }
+}
+
+impl EnvToken {
+    pub fn new(vars: &[&str]) -> Self {
+        Self {
+            vars: vars.iter().map(|var| var.to_string()).collect(),
+        }
+    }
+
+    fn token_from(&self, lookup: impl Fn(&str) -> Option<String>) -> Result<String, FlyError> {
+        self.vars
+            .iter()
+            .filter_map(|var| lookup(var))
+            .find(|token| !token.trim().is_empty())
+            .map(|token| token.trim().to_string())
+            .ok_or_else(|| FlyError::Config(format!("none of {} is set", self.vars.join(", "))))
+    }
+}
+
+impl TokenProvider for EnvToken {
+    fn token(&self) -> Result<String, FlyError> {
+        self.token_from(|var| std::env::var(var).ok())
+    }
+}
+
+/// Reads the `access_token` that `flyctl auth login` stores in `~/.fly/config.yml`.
+///
+/// The token is cached and the file read again only once its modification time changes,
+/// so tokens refreshed by flyctl are picked up. Clones share the cache.
+#[derive(Clone)]
+pub struct FlyctlConfigToken {
+    path: Option<PathBuf>,
+    cached: Arc<Mutex<Option<(SystemTime, String)>>>,
+}
+
+impl Default for FlyctlConfigToken {
+    fn default() -> Self {
+        let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+        Self {
+            path: home.map(|home| PathBuf::from(home).join(".fly").join("config.yml")),
+            cached: Arc::default(),
+        }
+    }
+}
+
+impl FlyctlConfigToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_path(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: Some(path.into()),
+            cached: Arc::default(),
+        }
+    }
+}
+
+impl TokenProvider for FlyctlConfigToken {
+    fn token(&self) -> Result<String, FlyError> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| FlyError::Config("could not locate the home directory".to_string()))?;
+        let read_error = |err: std::io::Error| {
+            FlyError::Config(format!("failed to read {}: {}", path.display(), err))
+        };
+        let modified = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(read_error)?;
+
+        let mut cached = self.cached.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some((cached_at, token)) = cached.as_ref() {
+            if *cached_at == modified {
+                return Ok(token.clone());
+            }
+        }
+
+        let config = std::fs::read_to_string(path).map_err(read_error)?;
+        let token = parse_access_token(&config)
+            .ok_or_else(|| FlyError::Config(format!("no access_token in {}", path.display())))?;
+        *cached = Some((modified, token.clone()));
+        Ok(token)
+    }
+}
+
+impl std::fmt::Debug for FlyctlConfigToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FlyctlConfigToken")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Reads the top-level `access_token: <value>` entry; a full YAML parser would be overkill.
+fn parse_access_token(config: &str) -> Option<String> {
+    config
+        .lines()
+        .find_map(|line| line.strip_prefix("access_token:"))
+        .map(|value| {
+            value
+                .trim()
+                .trim_matches(|c| c == '"' || c == '\'')
+                .to_string()
+        })
+        .filter(|token| !token.is_empty())
+}
+
+/// Tries each provider in turn and returns the first token found, or the last error.
+///
+/// The default chain reads [`EnvToken`] and then [`FlyctlConfigToken`], as
+/// [`FlyControl::from_env`](crate::FlyControl::from_env) does.
+#[derive(Clone)]
+pub struct TokenChain {
+    providers: Vec<Arc<dyn TokenProvider>>,
+}
+
+impl Default for TokenChain {
+    fn default() -> Self {
+        Self::new(EnvToken::default()).or(FlyctlConfigToken::default())
+    }
+}
+
+impl TokenChain {
+    pub fn new(first: impl TokenProvider + 'static) -> Self {
+        Self {
+            providers: vec![Arc::new(first)],
+        }
+    }
+
+    /// Falls back to `provider` when every provider before it fails.
+    pub fn or(mut self, provider: impl TokenProvider + 'static) -> Self {
+        self.providers.push(Arc::new(provider));
+        self
+    }
+}
+
+impl TokenProvider for TokenChain {
+    fn token(&self) -> Result<String, FlyError> {
+        let mut last_error = None;
+        for provider in &self.providers {
+            match provider.token() {
+                Ok(token) => return Ok(token),
+                Err(err) => last_error = Some(err),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| FlyError::Config("no token provider".to_string())))
+    }
+}
+
+impl std::fmt::Debug for TokenChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TokenChain(..)")
+    }
+}
+
+/// A token that can be replaced at runtime; clones share the same value.
+#[derive(Clone)]
+pub struct RefreshableToken {
+    token: Arc<RwLock<String>>,
+}
+
+impl RefreshableToken {
+    pub fn new(token: &str) -> Self {
+        Self {
+            token: Arc::new(RwLock::new(token.to_string())),
+        }
+    }
+
+    /// Replaces the token used by every request sent after this call.
+    pub fn set(&self, token: &str) {
+        *self.token.write().unwrap_or_else(|err| err.into_inner()) = token.to_string();
+    }
+}
+
+impl TokenProvider for RefreshableToken {
+    fn token(&self) -> Result<String, FlyError> {
+        Ok(self
+            .token
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone())
+    }
+}
+
+impl std::fmt::Debug for RefreshableToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RefreshableToken(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    #[test]
+    fn access_token_is_read_from_the_top_level_key() {
+        let config = "access_token: fo1_plain\nsend_metrics: true\n";
+        assert_eq!(parse_access_token(config).as_deref(), Some("fo1_plain"));
+
+        let config = "last_login: 2024-01-01\naccess_token: \"fo1_quoted\"\n";
+        assert_eq!(parse_access_token(config).as_deref(), Some("fo1_quoted"));
+        assert_eq!(
+            parse_access_token("access_token: 'fo1_single'").as_deref(),
+            Some("fo1_single")
+        );
+    }
+
+    #[test]
+    fn nested_missing_or_empty_tokens_are_ignored() {
+        let nested = "wireguard:\n  access_token: fo1_nested\naccess_token: fo1_top\n";
+        assert_eq!(parse_access_token(nested).as_deref(), Some("fo1_top"));
+        assert_eq!(
+            parse_access_token("wireguard:\n  access_token: fo1_nested\n"),
+            None
+        );
+        assert_eq!(parse_access_token("send_metrics: true\n"), None);
+        assert_eq!(parse_access_token("access_token: \"\"\n"), None);
+    }
+
+    #[test]
+    fn env_token_uses_the_first_non_empty_variable() {
+        let token = EnvToken::default();
+        let env = HashMap::from([
+            ("FLY_API_TOKEN", "  "),
+            ("FLY_ACCESS_TOKEN", " fo1_access\n"),
+        ]);
+        let lookup = |var: &str| env.get(var).map(|value| value.to_string());
+        assert_eq!(token.token_from(lookup).unwrap(), "fo1_access");
+
+        let env = HashMap::from([
+            ("FLY_API_TOKEN", "fo1_api"),
+            ("FLY_ACCESS_TOKEN", "fo1_access"),
+        ]);
+        let lookup = |var: &str| env.get(var).map(|value| value.to_string());
+        assert_eq!(token.token_from(lookup).unwrap(), "fo1_api");
+
+        let err = token.token_from(|_| None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid configuration: none of FLY_API_TOKEN, FLY_ACCESS_TOKEN is set"
+        );
+    }
+
+    #[test]
+    fn refreshed_tokens_reach_existing_clones() {
+        let token = RefreshableToken::new("first");
+        let clone = token.clone();
+        let provider: Arc<dyn TokenProvider> = Arc::new(token.clone());
+
+        token.set("second");
+
+        assert_eq!(clone.token().unwrap(), "second");
+        assert_eq!(provider.token().unwrap(), "second");
+    }
+
+    #[test]
+    fn token_chain_falls_back_at_request_time() {
+        let fallback = RefreshableToken::new("fo1_fallback");
+        let chain = TokenChain::new(|| -> Result<String, FlyError> {
+            Err(FlyError::Config("not set".to_string()))
+        })
+        .or(fallback.clone());
+        assert_eq!(chain.token().unwrap(), "fo1_fallback");
+
+        fallback.set("fo1_rotated");
+        assert_eq!(chain.token().unwrap(), "fo1_rotated");
+
+        let first = RefreshableToken::new("fo1_first");
+        let chain = TokenChain::new(first).or(fallback);
+        assert_eq!(chain.token().unwrap(), "fo1_first");
+
+        let chain = TokenChain::new(|| -> Result<String, FlyError> {
+            Err(FlyError::Config("first".to_string()))
+        })
+        .or(|| -> Result<String, FlyError> { Err(FlyError::Config("last".to_string())) });
+        assert_eq!(
+            chain.token().unwrap_err().to_string(),
+            "invalid configuration: last"
+        );
+    }
+
+    #[test]
+    fn flyctl_token_is_cached_until_the_file_changes() {
+        let path = std::env::temp_dir().join(format!(
+            "fly-sdk-config-{}-{}.yml",
+            std::process::id(),
+            fastrand::u64(..)
+        ));
+        std::fs::write(&path, "access_token: fo1_first\n").unwrap();
+        let provider = FlyctlConfigToken::with_path(&path);
+        assert_eq!(provider.token().unwrap(), "fo1_first");
+
+        // same modification time: the cached token is returned
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        std::fs::write(&path, "access_token: fo1_second\n").unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(modified).unwrap();
+        assert_eq!(provider.clone().token().unwrap(), "fo1_first");
+
+        file.set_modified(modified + Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(provider.token().unwrap(), "fo1_second");
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(provider.token().is_err());
+    }
+}
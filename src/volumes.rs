@@ -1,7 +1,12 @@
-use crate::{machines::MachineRegions, API_BASE_URL};
+use crate::client::{ApiClient, ApiRequest};
+use crate::error::FlyError;
+use crate::machines::MachineRegions;
+use crate::middleware::Middleware;
+use crate::retry::RetryPolicy;
+use crate::token::{StaticToken, TokenProvider};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::error::Error;
+use std::sync::Arc;
 use tracing::debug;
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -53,6 +58,7 @@ impl Default for Compute {
 }
 
 impl Compute {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         cpu_kind: Option<String>,
         cpus: Option<u32>,
@@ -197,89 +203,80 @@ pub struct Snapshot {
     pub status: String,
 }
 
+#[derive(Clone)]
 pub struct VolumeManager {
-    client: Client,
-    api_token: String,
+    api: ApiClient,
 }
 
 impl VolumeManager {
     pub fn new(client: Client, api_token: String) -> Self {
-        Self { client, api_token }
+        Self::from_api(ApiClient::new(
+            client,
+            Arc::new(StaticToken::new(&api_token)),
+        ))
+    }
+
+    pub(crate) fn from_api(api: ApiClient) -> Self {
+        Self { api }
+    }
+
+    /// Looks up the API token from `token_provider` for every request.
+    pub fn with_token_provider(mut self, token_provider: impl TokenProvider + 'static) -> Self {
+        self.api.set_token_provider(Arc::new(token_provider));
+        self
+    }
+
+    /// Sends requests to `base_url` instead of [`API_BASE_URL`](crate::API_BASE_URL).
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.api.set_base_url(base_url);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.api.set_retry_policy(retry_policy);
+        self
+    }
+
+    pub fn with_middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.api.push_middleware(Arc::new(middleware));
+        self
     }
 
     pub async fn list_volumes(
         &self,
         app_name: &str,
         summary: bool,
-    ) -> Result<Vec<Volume>, Box<dyn Error>> {
-        let url = format!(
-            "{API_BASE_URL}/apps/{}/volumes?summary={}",
-            app_name, summary
-        );
-        let response = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.api_token)
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            let volumes = response.json::<Vec<Volume>>().await?;
-            debug!("Successfully fetched volumes: {:?}", volumes);
-            Ok(volumes)
-        } else {
-            Err(format!("Failed to fetch volumes: {}", response.status()).into())
-        }
+    ) -> Result<Vec<Volume>, FlyError> {
+        let request = ApiRequest::get("/apps/{app}/volumes")
+            .param("app", app_name)
+            .query("summary", summary);
+
+        let volumes: Vec<Volume> = self.api.json(request).await?;
+        debug!("Successfully fetched volumes: {:?}", volumes);
+        Ok(volumes)
     }
 
     pub async fn create_volume(
         &self,
         app_name: &str,
         volume_request: CreateVolumeRequest,
-    ) -> Result<Volume, Box<dyn Error>> {
+    ) -> Result<Volume, FlyError> {
         debug!("Creating volume: {:?}", volume_request);
-        let url = format!("{API_BASE_URL}/apps/{}/volumes", app_name);
-
-        // let payload = serde_json::to_string(&volume_request)?;
-
-        let response = self
-            .client
-            .post(&url)
-            .bearer_auth(&self.api_token)
-            .json(&volume_request)
-            .send()
-            .await?;
-
-        let status = response.status();
-        if status.is_success() {
-            let volume = response.json::<Volume>().await?;
-            Ok(volume)
-        } else {
-            let error_text = response.text().await?;
-            Err(format!("Failed to create volume: {} - {}", status, error_text).into())
-        }
+        let request = ApiRequest::post("/apps/{app}/volumes")
+            .param("app", app_name)
+            .json(&volume_request);
+
+        self.api.json(request).await
     }
 
-    pub async fn get_volume(
-        &self,
-        app_name: &str,
-        volume_id: &str,
-    ) -> Result<Volume, Box<dyn Error>> {
-        let url = format!("{API_BASE_URL}/apps/{}/volumes/{}", app_name, volume_id);
-        let response = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.api_token)
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            let volume = response.json::<Volume>().await?;
-            debug!("Successfully fetched volume details: {:?}", volume);
-            Ok(volume)
-        } else {
-            Err(format!("Failed to fetch volume: {}", response.status()).into())
-        }
+    pub async fn get_volume(&self, app_name: &str, volume_id: &str) -> Result<Volume, FlyError> {
+        let request = ApiRequest::get("/apps/{app}/volumes/{volume}")
+            .param("app", app_name)
+            .param("volume", volume_id);
+
+        let volume: Volume = self.api.json(request).await?;
+        debug!("Successfully fetched volume details: {:?}", volume);
+        Ok(volume)
     }
 
     pub async fn update_volume(
@@ -287,44 +284,25 @@ impl VolumeManager {
         app_name: &str,
         volume_id: &str,
         update_request: UpdateVolumeRequest,
-    ) -> Result<Volume, Box<dyn Error>> {
-        let url = format!("{API_BASE_URL}/apps/{}/volumes/{}", app_name, volume_id);
-        let response = self
-            .client
-            .put(&url)
-            .bearer_auth(&self.api_token)
-            .json(&update_request)
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            let volume = response.json::<Volume>().await?;
-            debug!("Successfully updated volume: {:?}", volume);
-            Ok(volume)
-        } else {
-            Err(format!("Failed to update volume: {}", response.status()).into())
-        }
+    ) -> Result<Volume, FlyError> {
+        let request = ApiRequest::put("/apps/{app}/volumes/{volume}")
+            .param("app", app_name)
+            .param("volume", volume_id)
+            .json(&update_request);
+
+        let volume: Volume = self.api.json(request).await?;
+        debug!("Successfully updated volume: {:?}", volume);
+        Ok(volume)
     }
 
-    pub async fn destroy_volume(
-        &self,
-        app_name: &str,
-        volume_id: &str,
-    ) -> Result<(), Box<dyn Error>> {
-        let url = format!("{API_BASE_URL}/apps/{}/volumes/{}", app_name, volume_id);
-        let response = self
-            .client
-            .delete(&url)
-            .bearer_auth(&self.api_token)
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            debug!("Successfully deleted volume with ID: {}", volume_id);
-            Ok(())
-        } else {
-            Err(format!("Failed to delete volume: {}", response.status()).into())
-        }
+    pub async fn destroy_volume(&self, app_name: &str, volume_id: &str) -> Result<(), FlyError> {
+        let request = ApiRequest::delete("/apps/{app}/volumes/{volume}")
+            .param("app", app_name)
+            .param("volume", volume_id);
+
+        self.api.execute(request).await?;
+        debug!("Successfully deleted volume with ID: {}", volume_id);
+        Ok(())
     }
 
     pub async fn extend_volume(
@@ -332,74 +310,38 @@ impl VolumeManager {
         app_name: &str,
         volume_id: &str,
         extend_request: ExtendVolumeRequest,
-    ) -> Result<Volume, Box<dyn Error>> {
-        let url = format!(
-            "{API_BASE_URL}/apps/{}/volumes/{}/extend",
-            app_name, volume_id
-        );
-        let response = self
-            .client
-            .put(&url)
-            .bearer_auth(&self.api_token)
-            .json(&extend_request)
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            let volume = response.json::<Volume>().await?;
-            debug!("Successfully extended volume size: {:?}", volume);
-            Ok(volume)
-        } else {
-            Err(format!("Failed to extend volume size: {}", response.status()).into())
-        }
+    ) -> Result<Volume, FlyError> {
+        let request = ApiRequest::put("/apps/{app}/volumes/{volume}/extend")
+            .param("app", app_name)
+            .param("volume", volume_id)
+            .json(&extend_request);
+
+        let volume: Volume = self.api.json(request).await?;
+        debug!("Successfully extended volume size: {:?}", volume);
+        Ok(volume)
     }
 
     pub async fn list_snapshots(
         &self,
         app_name: &str,
         volume_id: &str,
-    ) -> Result<Vec<Snapshot>, Box<dyn Error>> {
-        let url = format!(
-            "{API_BASE_URL}/apps/{}/volumes/{}/snapshots",
-            app_name, volume_id
-        );
-        let response = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.api_token)
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            let snapshots = response.json::<Vec<Snapshot>>().await?;
-            debug!("Successfully fetched snapshots: {:?}", snapshots);
-            Ok(snapshots)
-        } else {
-            Err(format!("Failed to fetch snapshots: {}", response.status()).into())
-        }
+    ) -> Result<Vec<Snapshot>, FlyError> {
+        let request = ApiRequest::get("/apps/{app}/volumes/{volume}/snapshots")
+            .param("app", app_name)
+            .param("volume", volume_id);
+
+        let snapshots: Vec<Snapshot> = self.api.json(request).await?;
+        debug!("Successfully fetched snapshots: {:?}", snapshots);
+        Ok(snapshots)
     }
 
-    pub async fn create_snapshot(
-        &self,
-        app_name: &str,
-        volume_id: &str,
-    ) -> Result<(), Box<dyn Error>> {
-        let url = format!(
-            "{API_BASE_URL}/apps/{}/volumes/{}/snapshots",
-            app_name, volume_id
-        );
-        let response = self
-            .client
-            .post(&url)
-            .bearer_auth(&self.api_token)
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            debug!("Successfully created snapshot for volume: {}", volume_id);
-            Ok(())
-        } else {
-            Err(format!("Failed to create snapshot: {}", response.status()).into())
-        }
+    pub async fn create_snapshot(&self, app_name: &str, volume_id: &str) -> Result<(), FlyError> {
+        let request = ApiRequest::post("/apps/{app}/volumes/{volume}/snapshots")
+            .param("app", app_name)
+            .param("volume", volume_id);
+
+        self.api.execute(request).await?;
+        debug!("Successfully created snapshot for volume: {}", volume_id);
+        Ok(())
     }
 }
@@ -0,0 +1,44 @@
+use fly_sdk::testing::FakeFly;
+
+#[tokio::test]
+async fn creates_lists_and_deletes_apps() {
+    let fake = FakeFly::start().await;
+    let fly = fake.client();
+
+    let created = fly.apps.create("web", "personal").await.unwrap();
+    fly.apps.create("worker", "other-org").await.unwrap();
+
+    let apps = fly.apps.list("personal").await.unwrap();
+    assert_eq!(apps.len(), 1);
+    assert_eq!(apps[0].name, "web");
+    assert_eq!(apps[0].id, created.id);
+
+    fly.apps.delete("web", false).await.unwrap();
+    assert!(fly.apps.list("personal").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn duplicate_app_is_a_conflict() {
+    let fake = FakeFly::start().await;
+    let fly = fake.client();
+
+    fly.apps.create("web", "personal").await.unwrap();
+    let err = fly.apps.create("web", "personal").await.unwrap_err();
+
+    assert!(err.is_conflict());
+    assert!(err.request_id().is_some());
+}
+
+#[tokio::test]
+async fn deleting_a_missing_app_is_not_found() {
+    let fake = FakeFly::start().await;
+
+    let err = fake
+        .client()
+        .apps
+        .delete("missing", false)
+        .await
+        .unwrap_err();
+
+    assert!(err.is_not_found());
+}
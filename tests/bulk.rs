@@ -0,0 +1,118 @@
+use fly_sdk::machines::{
+    CreateManyOptions, MachineConfig, MachineRegions, MachineRequest, Replicas,
+};
+use fly_sdk::testing::FakeFly;
+
+fn worker() -> MachineRequest {
+    MachineRequest::new(
+        MachineConfig::builder().image("worker").build(),
+        Some("worker".to_string()),
+        None,
+    )
+}
+
+#[tokio::test]
+async fn creates_every_replica_with_unique_names() {
+    let fake = FakeFly::start().await;
+    let fly = fake.client();
+    fly.apps.create("jobs", "personal").await.unwrap();
+
+    let options = CreateManyOptions {
+        concurrency: 3,
+        ..CreateManyOptions::default()
+    };
+    let report = fly
+        .machines
+        .create_many("jobs", &worker(), 10, options)
+        .await
+        .unwrap();
+
+    assert!(report.all_created());
+    let names: Vec<_> = report
+        .created()
+        .map(|machine| machine.name.clone().unwrap())
+        .collect();
+    let expected: Vec<_> = (1..=10).map(|i| format!("worker-{i}")).collect();
+    assert_eq!(names, expected);
+    assert_eq!(fly.machines.list("jobs").await.unwrap().len(), 10);
+}
+
+#[tokio::test]
+async fn places_one_machine_per_region() {
+    let fake = FakeFly::start().await;
+    let fly = fake.client();
+    fly.apps.create("jobs", "personal").await.unwrap();
+
+    let regions = vec![
+        MachineRegions::Ams,
+        MachineRegions::Ord,
+        MachineRegions::Syd,
+    ];
+    let report = fly
+        .machines
+        .create_many("jobs", &worker(), regions, CreateManyOptions::default())
+        .await
+        .unwrap();
+
+    let placed: Vec<_> = report
+        .created()
+        .map(|machine| machine.region.clone().unwrap())
+        .collect();
+    assert_eq!(placed, ["ams", "ord", "syd"]);
+}
+
+#[tokio::test]
+async fn failures_are_reported_per_machine() {
+    let fake = FakeFly::start().await;
+    let fly = fake.client();
+    fly.apps.create("jobs", "personal").await.unwrap();
+    fake.fail_next_creates(2);
+
+    let options = CreateManyOptions {
+        concurrency: 1,
+        ..CreateManyOptions::default()
+    };
+    let report = fly
+        .machines
+        .create_many("jobs", &worker(), Replicas::Count(5), options)
+        .await
+        .unwrap();
+
+    assert!(!report.rolled_back);
+    assert_eq!(report.results.len(), 5);
+    assert!(matches!(report.results[0], Some(Err(_))));
+    assert!(matches!(report.results[1], Some(Err(_))));
+    assert_eq!(report.skipped(), 0);
+    assert!(report.failures().all(|err| err.is_conflict()));
+    assert_eq!(report.created().count(), 3);
+}
+
+#[tokio::test]
+async fn too_many_failures_roll_back_created_machines() {
+    let fake = FakeFly::start().await;
+    let fly = fake.client();
+    fly.apps.create("jobs", "personal").await.unwrap();
+    fake.fail_creates_after(2, 10);
+
+    let options = CreateManyOptions {
+        concurrency: 1,
+        rollback_after: Some(1),
+    };
+    let report = fly
+        .machines
+        .create_many("jobs", &worker(), 6, options)
+        .await
+        .unwrap();
+
+    assert!(report.rolled_back);
+    assert!(!report.all_created());
+    assert_eq!(report.created().count(), 2);
+    assert_eq!(report.failures().count(), 2);
+    assert_eq!(report.results.len(), 6);
+    assert_eq!(report.skipped(), 2);
+    assert!(report.results[4].is_none() && report.results[5].is_none());
+    for machine in report.created() {
+        let id = machine.id.as_deref().unwrap();
+        assert_eq!(fake.machine_state("jobs", id).as_deref(), Some("destroyed"));
+    }
+}
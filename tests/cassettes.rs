@@ -0,0 +1,146 @@
+use fly_sdk::machines::{MachineLifecycleState, MachineState, RestartPolicyEnum};
+use fly_sdk::secrets::SecretValue;
+use fly_sdk::testing::{Cassette, FakeFly, FAKE_TOKEN};
+use fly_sdk::{FlyControl, FlyError, RetryPolicy};
+use std::path::PathBuf;
+
+fn client(base_url: &str, token: &str) -> FlyControl {
+    FlyControl::builder()
+        .api_token(token)
+        .base_url(base_url)
+        .retry_policy(RetryPolicy::disabled())
+        .build()
+        .unwrap()
+}
+
+fn temp_cassette(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "fly-sdk-{}-{}-{}.json",
+        name,
+        std::process::id(),
+        fastrand::u64(..)
+    ))
+}
+
+#[tokio::test]
+async fn recorded_payloads_decode() {
+    let server = Cassette::new("tests/cassettes/machine_lifecycle.json")
+        .replay()
+        .await
+        .unwrap();
+    let fly = client(&server.base_url(), "unused");
+
+    let machine = fly
+        .machines
+        .get_machine("cassette-demo", "3d8d9014b32d38")
+        .await
+        .unwrap();
+    assert_eq!(machine.state, Some(MachineLifecycleState::Started));
+    assert_eq!(machine.events.unwrap().len(), 2);
+    let restart = machine.config.unwrap().restart.unwrap();
+    assert!(matches!(restart.policy, RestartPolicyEnum::OnFailure));
+
+    let volume = fly
+        .volumes
+        .get_volume("cassette-demo", "vol_4ykr9z0q3g1vwe8r")
+        .await
+        .unwrap();
+    assert_eq!(volume.size_gb, Some(1));
+
+    let err = fly
+        .machines
+        .wait_for_machine_state(
+            "cassette-demo",
+            "0000000000",
+            MachineState::Stopped,
+            Some(1),
+            None,
+        )
+        .await
+        .unwrap_err();
+    assert!(err.is_not_found());
+    assert_eq!(err.request_id(), Some("01J9ZJ6B2C3D4E5F6G7H8J9K0M-ams"));
+}
+
+#[tokio::test]
+async fn undecodable_bodies_keep_the_request_id() {
+    let path = temp_cassette("decode");
+    let interactions = serde_json::json!([{
+        "request": {"method": "GET", "path": "/apps/cassette-demo/machines/3d8d9014b32d38"},
+        "response": {
+            "status": 200,
+            "headers": {
+                "content-type": "application/json",
+                "fly-request-id": "01J9ZJ7C3D4E5F6G7H8J9K0M1N-ams"
+            },
+            "body": {"id": 42, "state": ["started"]}
+        }
+    }]);
+    std::fs::write(&path, interactions.to_string()).unwrap();
+    let server = Cassette::new(&path).replay().await.unwrap();
+
+    let err = client(&server.base_url(), "unused")
+        .machines
+        .get_machine("cassette-demo", "3d8d9014b32d38")
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, FlyError::Decode { .. }), "{err}");
+    assert_eq!(err.request_id(), Some("01J9ZJ7C3D4E5F6G7H8J9K0M1N-ams"));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn unrecorded_requests_are_rejected() {
+    let server = Cassette::new("tests/cassettes/machine_lifecycle.json")
+        .replay()
+        .await
+        .unwrap();
+
+    let err = client(&server.base_url(), "unused")
+        .apps
+        .list("personal")
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.status().map(|status| status.as_u16()), Some(501));
+}
+
+#[tokio::test]
+async fn records_redacted_cassettes_that_replay_offline() {
+    let path = temp_cassette("record");
+    std::fs::write(&path, "stale").unwrap();
+    let fake = FakeFly::start().await;
+    let server = Cassette::new(&path)
+        .redact_field("label")
+        .record(&fake.base_url())
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap().trim(), "[]");
+    let fly = client(&server.base_url(), FAKE_TOKEN);
+
+    fly.apps.create("web", "personal").await.unwrap();
+    fly.secrets
+        .create_secret("web", "db-key", "secret_key", SecretValue::new(vec![4, 2]))
+        .await
+        .unwrap();
+    let secret = &server.interactions()[1];
+    assert_eq!(secret.request.body.as_ref().unwrap()["value"], "[REDACTED]");
+    assert_eq!(
+        secret.response.body.as_ref().unwrap()["label"],
+        "[REDACTED]"
+    );
+    drop(server);
+    drop(fake);
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert!(!contents.contains(FAKE_TOKEN));
+
+    let server = Cassette::new(&path).replay().await.unwrap();
+    assert_eq!(server.interactions().len(), 2);
+    let fly = client(&server.base_url(), "unused");
+    let app = fly.apps.create("web", "personal").await.unwrap();
+    assert!(!app.id.is_empty());
+
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use common::launch;
+use fly_sdk::machines::{MachineConfig, MachineRequest};
+use fly_sdk::middleware::SetHeader;
+use fly_sdk::rate_limit::{Limit, RateLimitAction, RateLimits};
+use fly_sdk::testing::{FakeFly, FAKE_TOKEN};
+use fly_sdk::{FlyControl, RetryPolicy};
+use reqwest::StatusCode;
+use std::time::{Duration, Instant, SystemTime};
+
+#[tokio::test]
+async fn wrong_token_is_unauthorized() {
+    let fake = FakeFly::start().await;
+    let fly = FlyControl::builder()
+        .api_token("not-the-token")
+        .base_url(&fake.base_url())
+        .build()
+        .unwrap();
+
+    let err = fly.apps.list("personal").await.unwrap_err();
+
+    assert!(err.is_unauthorized());
+    assert_eq!(err.api_error().unwrap().message, "unauthorized");
+}
+
+#[tokio::test]
+async fn middleware_runs_against_the_fake() {
+    let fake = FakeFly::start().await;
+    let apps = fake
+        .client()
+        .apps
+        .with_middleware(SetHeader::new("x-trace", "1").unwrap());
+
+    apps.create("web", "personal").await.unwrap();
+
+    assert_eq!(apps.list("personal").await.unwrap().len(), 1);
+}
+
+fn retrying_client(fake: &FakeFly, policy: RetryPolicy) -> FlyControl {
+    FlyControl::builder()
+        .api_token(FAKE_TOKEN)
+        .base_url(&fake.base_url())
+        .retry_policy(policy)
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn throttled_and_failed_reads_are_retried() {
+    let fake = FakeFly::start().await;
+    let fly = retrying_client(
+        &fake,
+        RetryPolicy::new().backoff(Duration::from_millis(50), Duration::from_millis(300)),
+    );
+    fake.fail_next_requests("/apps", 1, StatusCode::TOO_MANY_REQUESTS, Some("3600"));
+    fake.fail_next_requests("/apps", 1, StatusCode::SERVICE_UNAVAILABLE, None);
+
+    let started_at = Instant::now();
+    let apps = fly.apps.list("personal").await.unwrap();
+
+    assert!(apps.is_empty());
+    assert_eq!(fake.request_count("/apps"), 3);
+    // Retry-After is capped at the maximum backoff
+    assert!(started_at.elapsed() < Duration::from_secs(2));
+}
+
+#[tokio::test]
+async fn retry_after_dates_are_honored() {
+    let fake = FakeFly::start().await;
+    let fly = retrying_client(
+        &fake,
+        RetryPolicy::new().backoff(Duration::from_millis(10), Duration::from_secs(5)),
+    );
+    let retry_at = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(2));
+    fake.fail_next_requests("/apps", 1, StatusCode::TOO_MANY_REQUESTS, Some(&retry_at));
+
+    let started_at = Instant::now();
+    fly.apps.list("personal").await.unwrap();
+
+    // the date has whole-second precision
+    assert!(started_at.elapsed() >= Duration::from_millis(900));
+    assert_eq!(fake.request_count("/apps"), 2);
+}
+
+#[tokio::test]
+async fn mutations_are_not_retried_by_default() {
+    let fake = FakeFly::start().await;
+    let fly = retrying_client(&fake, RetryPolicy::new());
+    fake.fail_next_requests("/apps", 1, StatusCode::SERVICE_UNAVAILABLE, None);
+
+    let err = fly.apps.create("web", "personal").await.unwrap_err();
+
+    assert_eq!(err.status(), Some(StatusCode::SERVICE_UNAVAILABLE));
+    assert_eq!(fake.request_count("/apps"), 1);
+}
+
+#[tokio::test]
+async fn retry_mutations_only_repeats_safe_machine_actions() {
+    let fake = FakeFly::start().await;
+    let fly = retrying_client(
+        &fake,
+        RetryPolicy::new()
+            .retry_mutations(true)
+            .backoff(Duration::from_millis(10), Duration::from_millis(20)),
+    );
+    let (_, id, instance_id) = launch(&fake).await;
+
+    let creates_before = fake.request_count("/machines");
+    fake.fail_next_requests("/machines", 1, StatusCode::SERVICE_UNAVAILABLE, None);
+    let config = MachineConfig::builder().image("nginx").build();
+    let err = fly
+        .machines
+        .create("web", MachineRequest::new(config, None, None))
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::SERVICE_UNAVAILABLE));
+    assert_eq!(fake.request_count("/machines"), creates_before + 1);
+
+    fake.fail_next_requests("/suspend", 1, StatusCode::SERVICE_UNAVAILABLE, None);
+    fly.machines
+        .suspend("web", &id, &instance_id)
+        .await
+        .unwrap();
+    assert_eq!(fake.request_count("/suspend"), 2);
+}
+
+#[tokio::test]
+async fn retries_stop_after_max_attempts() {
+    let fake = FakeFly::start().await;
+    let fly = retrying_client(
+        &fake,
+        RetryPolicy::new()
+            .max_attempts(2)
+            .backoff(Duration::from_millis(10), Duration::from_millis(20)),
+    );
+    fake.fail_next_requests("/apps", 5, StatusCode::BAD_GATEWAY, None);
+
+    let err = fly.apps.list("personal").await.unwrap_err();
+
+    assert_eq!(err.status(), Some(StatusCode::BAD_GATEWAY));
+    assert_eq!(fake.request_count("/apps"), 2);
+}
+
+#[tokio::test]
+async fn clones_share_rate_limits_and_retries_take_tokens() {
+    let fake = FakeFly::start().await;
+    let fly = FlyControl::builder()
+        .api_token(FAKE_TOKEN)
+        .base_url(&fake.base_url())
+        .rate_limits(RateLimits::unlimited().limit(RateLimitAction::Read, Limit::new(5.0, 2)))
+        .retry_policy(
+            RetryPolicy::new().backoff(Duration::from_millis(1), Duration::from_millis(1)),
+        )
+        .build()
+        .unwrap();
+    fly.apps.create("web", "personal").await.unwrap();
+
+    let started_at = Instant::now();
+    // the burst of 2 is shared by the clone, and the retry after a 503 needs a token too
+    fake.fail_next_requests("/machines", 1, StatusCode::SERVICE_UNAVAILABLE, None);
+    fly.machines.list("web").await.unwrap();
+    fly.clone().machines.list("web").await.unwrap();
+
+    // 3 attempts with a burst of 2 at 5 per second
+    assert!(started_at.elapsed() >= Duration::from_millis(180));
+    assert_eq!(fake.request_count("/machines"), 3);
+}
@@ -0,0 +1,118 @@
+use fly_sdk::machines::{CloneOptions, MachineConfig, MachineRegions, MachineRequest, MountConfig};
+use fly_sdk::testing::FakeFly;
+use fly_sdk::volumes::CreateVolumeRequest;
+use fly_sdk::FlyControl;
+
+async fn launch_with_volume(fake: &FakeFly) -> (FlyControl, String, String) {
+    let fly = fake.client();
+    fly.apps.create("db", "personal").await.unwrap();
+    let volume = fly
+        .volumes
+        .create_volume(
+            "db",
+            CreateVolumeRequest::builder("pg_data", MachineRegions::Ams, 10).build(),
+        )
+        .await
+        .unwrap();
+    let volume_id = volume.id.unwrap();
+    let config = MachineConfig::builder()
+        .image("postgres")
+        .add_env("ROLE", "primary")
+        .add_mount(MountConfig {
+            add_size_gb: None,
+            encrypted: None,
+            extend_threshold_percent: None,
+            name: Some("pg_data".to_string()),
+            path: "/data".to_string(),
+            size_gb: None,
+            size_gb_limit: None,
+            volume: volume_id.clone(),
+        })
+        .build();
+    let machine = fly
+        .machines
+        .create(
+            "db",
+            MachineRequest::new(config, None, Some(MachineRegions::Ams)),
+        )
+        .await
+        .unwrap();
+    (fly, machine.id.unwrap(), volume_id)
+}
+
+#[tokio::test]
+async fn clone_into_another_region_with_a_new_volume() {
+    let fake = FakeFly::start().await;
+    let (fly, source_id, source_volume) = launch_with_volume(&fake).await;
+
+    let options = CloneOptions {
+        region: Some(MachineRegions::Fra),
+        name: Some("pg-fra".to_string()),
+        attach_new_volume: true,
+        ..CloneOptions::default()
+    }
+    .with_config_overrides(|config| {
+        config
+            .env
+            .get_or_insert_with(Default::default)
+            .insert("ROLE".to_string(), "replica".to_string());
+    });
+    let clone = fly
+        .machines
+        .clone_machine("db", &source_id, options)
+        .await
+        .unwrap();
+
+    assert_eq!(clone.region.as_deref(), Some("fra"));
+    assert_eq!(clone.name.as_deref(), Some("pg-fra"));
+    let config = clone.config.unwrap();
+    assert_eq!(config.image, "postgres");
+    assert_eq!(config.env.unwrap()["ROLE"], "replica");
+    let new_volume = &config.mounts.unwrap()[0].volume;
+    assert_ne!(new_volume, &source_volume);
+
+    let volume = fly.volumes.get_volume("db", new_volume).await.unwrap();
+    assert_eq!(volume.region.as_deref(), Some("fra"));
+    assert_eq!(volume.size_gb, Some(10));
+    assert_eq!(volume.attached_machine_id, clone.id);
+}
+
+#[tokio::test]
+async fn clone_with_volumes_needs_attach_new_volume() {
+    let fake = FakeFly::start().await;
+    let (fly, source_id, _) = launch_with_volume(&fake).await;
+
+    let err = fly
+        .machines
+        .clone_machine("db", &source_id, CloneOptions::default())
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, fly_sdk::FlyError::Config(_)));
+    assert_eq!(fly.machines.list("db").await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn failed_clone_destroys_its_volumes() {
+    let fake = FakeFly::start().await;
+    let (fly, source_id, _) = launch_with_volume(&fake).await;
+    fake.fail_next_creates(1);
+
+    let options = CloneOptions {
+        attach_new_volume: true,
+        ..CloneOptions::default()
+    };
+    let err = fly
+        .machines
+        .clone_machine("db", &source_id, options)
+        .await
+        .unwrap_err();
+
+    assert!(err.is_conflict());
+    let volumes = fly.volumes.list_volumes("db", false).await.unwrap();
+    let live: Vec<_> = volumes
+        .iter()
+        .filter(|volume| volume.state.as_deref() != Some("destroyed"))
+        .collect();
+    assert_eq!(live.len(), 1);
+}
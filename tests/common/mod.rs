@@ -0,0 +1,23 @@
+use fly_sdk::machines::{MachineConfig, MachineRequest, MachineState};
+use fly_sdk::testing::FakeFly;
+use fly_sdk::FlyControl;
+
+/// Creates the `web` app with one started nginx machine, returning its id and instance id.
+pub async fn launch(fake: &FakeFly) -> (FlyControl, String, String) {
+    let fly = fake.client();
+    fly.apps.create("web", "personal").await.unwrap();
+    let machine = fly
+        .machines
+        .create(
+            "web",
+            MachineRequest::new(MachineConfig::builder().image("nginx").build(), None, None),
+        )
+        .await
+        .unwrap();
+    let id = machine.id.unwrap();
+    fly.machines
+        .wait_for_machine_state("web", &id, MachineState::Started, Some(5), None)
+        .await
+        .unwrap();
+    (fly, id, machine.instance_id.unwrap())
+}
@@ -0,0 +1,218 @@
+use fly_sdk::machines::{
+    CheckType, Checks, DeployEvent, MachineConfig, MachineLifecycleState, MachineRequest,
+    MachineState, RollingDeploy,
+};
+use fly_sdk::testing::FakeFly;
+use fly_sdk::FlyControl;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Creates the `web` app with `count` started nginx machines that define a health check.
+async fn launch_checked(fake: &FakeFly, count: usize) -> (FlyControl, Vec<String>) {
+    let fly = fake.client();
+    fly.apps.create("web", "personal").await.unwrap();
+    let check = Checks::builder()
+        .check_type(CheckType::Http)
+        .port(8080)
+        .path("/health")
+        .build();
+    let mut ids = Vec::new();
+    for _ in 0..count {
+        let config = MachineConfig::builder()
+            .image("nginx")
+            .checks(check.clone())
+            .build();
+        let machine = fly
+            .machines
+            .create("web", MachineRequest::new(config, None, None))
+            .await
+            .unwrap();
+        let id = machine.id.unwrap();
+        fly.machines
+            .wait_for_machine_state("web", &id, MachineState::Started, Some(5), None)
+            .await
+            .unwrap();
+        ids.push(id);
+    }
+    ids.sort();
+    (fly, ids)
+}
+
+fn recorder() -> (
+    Arc<Mutex<Vec<DeployEvent>>>,
+    impl Fn(&DeployEvent) + Send + Sync,
+) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    (events, move |event: &DeployEvent| {
+        sink.lock().unwrap().push(event.clone())
+    })
+}
+
+async fn image_of(fly: &FlyControl, id: &str) -> String {
+    let machine = fly.machines.get_machine("web", id).await.unwrap();
+    machine.config.unwrap().image
+}
+
+#[tokio::test]
+async fn deploy_updates_machines_in_batches() {
+    let fake = FakeFly::start().await;
+    let (fly, ids) = launch_checked(&fake, 4).await;
+    let stopped = fly.machines.get_machine("web", &ids[3]).await.unwrap();
+    fly.machines
+        .stop("web", &ids[3], stopped.instance_id.as_deref().unwrap())
+        .await
+        .unwrap();
+    fly.machines
+        .wait_for_machine_state("web", &ids[3], MachineState::Stopped, Some(5), None)
+        .await
+        .unwrap();
+
+    let (events, on_progress) = recorder();
+    let report = fly
+        .machines
+        .deploy(
+            "web",
+            |config| config.image = "nginx:2".to_string(),
+            RollingDeploy::new()
+                .max_unavailable(2)
+                .on_progress(on_progress),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(report.machine_ids, ids);
+    for id in &ids {
+        assert_eq!(image_of(&fly, id).await, "nginx:2");
+    }
+    let machine = fly.machines.get_machine("web", &ids[3]).await.unwrap();
+    assert_eq!(machine.state, Some(MachineLifecycleState::Stopped));
+
+    let events = events.lock().unwrap();
+    assert_eq!(
+        events.first(),
+        Some(&DeployEvent::Started {
+            machines: 4,
+            batches: 2
+        })
+    );
+    assert_eq!(events.last(), Some(&DeployEvent::Finished { updated: 4 }));
+    let batches: Vec<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            DeployEvent::BatchStarted { batch, machine_ids } => Some((*batch, machine_ids.clone())),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        batches,
+        vec![(1, ids[..2].to_vec()), (2, ids[2..].to_vec())]
+    );
+    let ready = events
+        .iter()
+        .filter(|event| matches!(event, DeployEvent::MachineReady { .. }))
+        .count();
+    assert_eq!(ready, 4);
+}
+
+#[tokio::test]
+async fn failing_checks_stop_the_deploy_and_roll_back() {
+    let fake = FakeFly::start().await;
+    let (fly, ids) = launch_checked(&fake, 3).await;
+    let untouched = fly.machines.get_machine("web", &ids[2]).await.unwrap();
+    fake.set_unhealthy_image("nginx:broken");
+
+    let (events, on_progress) = recorder();
+    let err = fly
+        .machines
+        .deploy(
+            "web",
+            |config| config.image = "nginx:broken".to_string(),
+            RollingDeploy::new()
+                .max_unavailable(2)
+                .health_timeout(Duration::from_millis(500))
+                .on_progress(on_progress),
+        )
+        .await
+        .unwrap_err();
+
+    assert!(err.is_health_check_failed(), "{err}");
+    for id in &ids {
+        assert_eq!(image_of(&fly, id).await, "nginx");
+    }
+    let machine = fly.machines.get_machine("web", &ids[2]).await.unwrap();
+    assert_eq!(machine.instance_id, untouched.instance_id);
+    assert_eq!(machine.state, Some(MachineLifecycleState::Started));
+
+    let events = events.lock().unwrap();
+    assert!(!events
+        .iter()
+        .any(|event| matches!(event, DeployEvent::BatchStarted { batch: 2, .. })));
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, DeployEvent::BatchFailed { batch: 1, .. })));
+    assert!(events.contains(&DeployEvent::RollingBack {
+        machine_ids: ids[..2].to_vec()
+    }));
+    let rolled_back = events
+        .iter()
+        .filter(|event| matches!(event, DeployEvent::MachineRolledBack { .. }))
+        .count();
+    assert_eq!(rolled_back, 2);
+    assert!(!events
+        .iter()
+        .any(|event| matches!(event, DeployEvent::Finished { .. })));
+}
+
+#[tokio::test]
+async fn machines_that_fail_to_boot_are_rolled_back() {
+    let fake = FakeFly::start().await;
+    let (fly, ids) = launch_checked(&fake, 2).await;
+    fake.set_unbootable_image("nginx:crash");
+
+    let (events, on_progress) = recorder();
+    let err = fly
+        .machines
+        .deploy(
+            "web",
+            |config| config.image = "nginx:crash".to_string(),
+            RollingDeploy::new()
+                .health_timeout(Duration::from_millis(500))
+                .on_progress(on_progress),
+        )
+        .await
+        .unwrap_err();
+
+    assert!(err.is_timeout(), "{err}");
+    for id in &ids {
+        assert_eq!(image_of(&fly, id).await, "nginx");
+        let machine = fly.machines.get_machine("web", id).await.unwrap();
+        assert_eq!(machine.state, Some(MachineLifecycleState::Started));
+    }
+    let events = events.lock().unwrap();
+    assert!(events.contains(&DeployEvent::RollingBack {
+        machine_ids: ids[..1].to_vec()
+    }));
+    assert!(events.contains(&DeployEvent::MachineRolledBack {
+        machine_id: ids[0].clone()
+    }));
+}
+
+#[tokio::test]
+async fn deploy_needs_at_least_one_machine_per_batch() {
+    let fake = FakeFly::start().await;
+    let (fly, ids) = launch_checked(&fake, 1).await;
+
+    let err = fly
+        .machines
+        .deploy(
+            "web",
+            |config| config.image = "nginx:2".to_string(),
+            RollingDeploy::new().max_unavailable(0),
+        )
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, fly_sdk::FlyError::Config(_)), "{err}");
+    assert_eq!(image_of(&fly, &ids[0]).await, "nginx");
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::launch;
+use fly_sdk::machines::{MachineEventKind, WatchOptions};
+use fly_sdk::testing::FakeFly;
+use futures_util::StreamExt;
+use std::time::Duration;
+
+fn fast() -> WatchOptions {
+    WatchOptions {
+        poll_interval: Duration::from_millis(20),
+        ..WatchOptions::default()
+    }
+}
+
+#[tokio::test]
+async fn list_events_reads_the_event_type() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+
+    let events = fly.machines.list_events("web", &id).await.unwrap();
+
+    let types: Vec<_> = events
+        .iter()
+        .filter_map(|e| e.event_type.as_deref())
+        .collect();
+    assert_eq!(types, ["start", "launch"]);
+}
+
+#[tokio::test]
+async fn watch_yields_only_new_events_in_order() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+    let events = fly
+        .machines
+        .watch_events_with("web", &id, fast())
+        .await
+        .unwrap();
+    futures_util::pin_mut!(events);
+
+    fake.crash("web", &id, 137);
+    fly.machines.start("web", &id).await.unwrap();
+
+    let crash = events.next().await.unwrap().unwrap();
+    assert_eq!(
+        crash.kind,
+        MachineEventKind::Exit {
+            exit_code: Some(137),
+            oom_killed: false,
+            requested_stop: false,
+        }
+    );
+    let start = events.next().await.unwrap().unwrap();
+    assert_eq!(start.kind, MachineEventKind::Start);
+    assert_eq!(start.status.as_deref(), Some("started"));
+    assert_ne!(crash.id, start.id);
+}
+
+#[tokio::test]
+async fn watch_can_replay_existing_events_and_ends_on_destroy() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+    let options = WatchOptions {
+        include_existing: true,
+        ..fast()
+    };
+    let events = fly
+        .machines
+        .watch_events_with("web", &id, options)
+        .await
+        .unwrap();
+    futures_util::pin_mut!(events);
+
+    assert_eq!(
+        events.next().await.unwrap().unwrap().kind,
+        MachineEventKind::Launch
+    );
+    assert_eq!(
+        events.next().await.unwrap().unwrap().kind,
+        MachineEventKind::Start
+    );
+
+    fly.machines.delete("web", &id, true).await.unwrap();
+    let mut kinds = Vec::new();
+    while let Some(event) = events.next().await {
+        kinds.push(event.unwrap().kind);
+    }
+    assert_eq!(kinds.last(), Some(&MachineEventKind::Destroy));
+}
+
+#[tokio::test]
+async fn watch_fails_for_a_missing_machine() {
+    let fake = FakeFly::start().await;
+    let (fly, _, _) = launch(&fake).await;
+
+    let err = fly
+        .machines
+        .watch_events("web", "missing")
+        .await
+        .err()
+        .unwrap();
+
+    assert!(err.is_not_found());
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use common::launch;
+use fly_sdk::machines::ExecRequest;
+use fly_sdk::testing::{ExecReply, FakeFly};
+use fly_sdk::FlyError;
+use std::time::Duration;
+
+#[tokio::test]
+async fn exec_passes_stdin_to_the_command() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+    fake.set_exec_handler(|command, stdin| ExecReply {
+        stdout: format!("{}: {}", command.join(" "), stdin.unwrap_or_default()),
+        ..Default::default()
+    });
+
+    let request = ExecRequest::new(["psql", "-f", "-"])
+        .stdin("select 1;")
+        .timeout(Duration::from_millis(1500));
+    let output = fly.machines.exec("web", &id, request).await.unwrap();
+
+    assert!(output.success());
+    assert_eq!(output.stdout_lossy(), "psql -f -: select 1;");
+    assert!(output.stderr.is_empty());
+}
+
+#[tokio::test]
+async fn check_turns_failures_into_errors() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+    fake.set_exec_handler(|command, _| match command[0].as_str() {
+        "migrate" => ExecReply {
+            exit_code: Some(3),
+            stderr: "relation \"users\" already exists\n".to_string(),
+            ..Default::default()
+        },
+        _ => ExecReply {
+            exit_code: Some(137),
+            exit_signal: Some(9),
+            ..Default::default()
+        },
+    });
+
+    let output = fly
+        .machines
+        .exec("web", &id, ExecRequest::new(["migrate"]))
+        .await
+        .unwrap();
+    assert_eq!(output.exit_code, Some(3));
+    let err = output.check().unwrap_err();
+    assert!(err.is_command_failed());
+    assert_eq!(
+        err.to_string(),
+        "command exited with code 3: relation \"users\" already exists"
+    );
+
+    let err = fly
+        .machines
+        .exec("web", &id, ExecRequest::new(["sleep", "600"]))
+        .await
+        .unwrap()
+        .check()
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        FlyError::CommandFailed {
+            exit_signal: Some(9),
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn base64_stdout_is_decoded_to_bytes() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+    fake.set_exec_handler(|command, _| ExecReply {
+        stdout: match command[0].as_str() {
+            "good" => "/wD+AQ==\n".to_string(),
+            _ => "not base64".to_string(),
+        },
+        ..Default::default()
+    });
+
+    let output = fly
+        .machines
+        .exec("web", &id, ExecRequest::new(["good"]).base64_stdout())
+        .await
+        .unwrap();
+    assert_eq!(output.stdout, [0xff, 0x00, 0xfe, 0x01]);
+
+    let err = fly
+        .machines
+        .exec("web", &id, ExecRequest::new(["bad"]).base64_stdout())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, FlyError::Decode { .. }));
+}
+
+#[tokio::test]
+async fn a_missing_exit_code_is_not_success() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+    fake.set_exec_handler(|_, _| ExecReply {
+        exit_code: None,
+        ..Default::default()
+    });
+
+    let output = fly
+        .machines
+        .exec("web", &id, ExecRequest::new(["true"]))
+        .await
+        .unwrap();
+
+    assert_eq!(output.exit_code, None);
+    assert!(!output.success());
+    let err = output.check().unwrap_err();
+    assert_eq!(err.to_string(), "command finished without an exit code");
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use common::launch;
+use fly_sdk::machines::{MachineConfig, MachineRequest};
+use fly_sdk::testing::FakeFly;
+use std::time::Duration;
+
+fn caddy() -> MachineRequest {
+    MachineRequest::new(MachineConfig::builder().image("caddy").build(), None, None)
+}
+
+#[tokio::test]
+async fn lease_blocks_other_writers() {
+    let fake = FakeFly::start().await;
+    let (fly, id, instance_id) = launch(&fake).await;
+
+    let lease = fly
+        .machines
+        .acquire_lease("web", &id, 30, Some("deploy"))
+        .await
+        .unwrap();
+    assert_eq!(lease.lease().description.as_deref(), Some("deploy"));
+
+    let err = fly
+        .machines
+        .update_machine("web", &id, &instance_id, caddy())
+        .await
+        .unwrap_err();
+    assert!(err.is_conflict());
+    let err = fly
+        .machines
+        .acquire_lease("web", &id, 30, None)
+        .await
+        .unwrap_err();
+    assert!(err.is_conflict());
+
+    fly.machines
+        .with_lease(&lease)
+        .update_machine("web", &id, &instance_id, caddy())
+        .await
+        .unwrap();
+
+    lease.release().await.unwrap();
+    let err = fly.machines.get_lease("web", &id).await.unwrap_err();
+    assert!(err.is_not_found());
+}
+
+#[tokio::test]
+async fn dropping_the_guard_releases_the_lease() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+
+    let lease = fly
+        .machines
+        .acquire_lease("web", &id, 30, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        fly.machines.get_lease("web", &id).await.unwrap().nonce,
+        lease.nonce()
+    );
+    drop(lease);
+
+    for _ in 0..50 {
+        match fly.machines.get_lease("web", &id).await {
+            Err(err) if err.is_not_found() => return,
+            _ => tokio::time::sleep(Duration::from_millis(20)).await,
+        }
+    }
+    panic!("lease was not released after the guard was dropped");
+}
+
+#[tokio::test]
+async fn guard_refreshes_the_lease() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+
+    let lease = fly
+        .machines
+        .acquire_lease("web", &id, 2, None)
+        .await
+        .unwrap();
+    let first_expiry = lease.lease().expires_at;
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+
+    assert!(lease.lease().expires_at > first_expiry);
+    let current = fly.machines.get_lease("web", &id).await.unwrap();
+    assert_eq!(current.expires_at, lease.lease().expires_at);
+}
@@ -0,0 +1,96 @@
+use fly_sdk::machines::{
+    ListMachinesQuery, MachineConfig, MachineLifecycleState, MachineRegions, MachineRequest,
+};
+use fly_sdk::testing::FakeFly;
+use fly_sdk::FlyControl;
+
+async fn fleet(fake: &FakeFly) -> FlyControl {
+    let fly = fake.client();
+    fly.apps.create("web", "personal").await.unwrap();
+    for (name, region, group) in [
+        ("app-ams", MachineRegions::Ams, "app"),
+        ("app-ord", MachineRegions::Ord, "app"),
+        ("worker-ams", MachineRegions::Ams, "worker"),
+        ("batch-ams", MachineRegions::Ams, "worker"),
+    ] {
+        let config = MachineConfig::builder().image("nginx").build();
+        let request = MachineRequest::new(config, Some(name.to_string()), Some(region));
+        let machine = fly.machines.create("web", request).await.unwrap();
+        fly.machines
+            .set_metadata_key(
+                "web",
+                machine.id.as_deref().unwrap(),
+                "fly_process_group",
+                group,
+            )
+            .await
+            .unwrap();
+    }
+    fly
+}
+
+fn names(machines: &[fly_sdk::machines::MachineResponse]) -> Vec<&str> {
+    let mut names: Vec<_> = machines
+        .iter()
+        .filter_map(|machine| machine.name.as_deref())
+        .collect();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn filters_by_region_and_process_group() {
+    let fake = FakeFly::start().await;
+    let fly = fleet(&fake).await;
+
+    let query = ListMachinesQuery::new()
+        .region(MachineRegions::Ams)
+        .process_group("worker");
+    let machines = fly.machines.list_with("web", query).await.unwrap();
+
+    assert_eq!(names(&machines), ["batch-ams", "worker-ams"]);
+}
+
+#[tokio::test]
+async fn filters_by_state_and_includes_deleted_on_request() {
+    let fake = FakeFly::start().await;
+    let fly = fleet(&fake).await;
+    let machines = fly.machines.list("web").await.unwrap();
+    let app_ord = machines
+        .iter()
+        .find(|machine| machine.name.as_deref() == Some("app-ord"))
+        .unwrap();
+    fly.machines
+        .delete("web", app_ord.id.as_deref().unwrap(), true)
+        .await
+        .unwrap();
+
+    let started = ListMachinesQuery::new().state(MachineLifecycleState::Started);
+    assert_eq!(
+        fly.machines.list_with("web", started).await.unwrap().len(),
+        3
+    );
+
+    let destroyed = ListMachinesQuery::new()
+        .include_deleted(true)
+        .state(MachineLifecycleState::Destroyed);
+    let machines = fly.machines.list_with("web", destroyed).await.unwrap();
+    assert_eq!(names(&machines), ["app-ord"]);
+}
+
+#[tokio::test]
+async fn summary_omits_config_and_predicate_runs_client_side() {
+    let fake = FakeFly::start().await;
+    let fly = fleet(&fake).await;
+
+    let query = ListMachinesQuery::new().summary(true).filter(|machine| {
+        machine
+            .name
+            .as_deref()
+            .is_some_and(|name| name.ends_with("-ams"))
+    });
+    let machines = fly.machines.list_with("web", query).await.unwrap();
+
+    assert_eq!(names(&machines), ["app-ams", "batch-ams", "worker-ams"]);
+    assert!(machines.iter().all(|machine| machine.config.is_none()));
+}
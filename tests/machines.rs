@@ -0,0 +1,517 @@
+mod common;
+
+use common::launch;
+use fly_sdk::machines::{
+    DrainOptions, MachineConfig, MachineLifecycleState, MachineRequest, MachineState, Signal,
+    StopConfig, StopOptions, TimeoutConfig,
+};
+use fly_sdk::testing::{ExecReply, FakeFly, FAKE_TOKEN};
+use fly_sdk::{FlyControl, FlyError};
+use reqwest::StatusCode;
+use std::time::Duration;
+
+#[tokio::test]
+async fn created_machines_start() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+
+    let machine = fly.machines.get_machine("web", &id).await.unwrap();
+    assert_eq!(machine.state, Some(MachineLifecycleState::Started));
+    assert_eq!(machine.config.unwrap().image, "nginx");
+    assert_eq!(fly.machines.list("web").await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn skip_launch_leaves_machine_created() {
+    let fake = FakeFly::start().await;
+    let fly = fake.client();
+    fly.apps.create("web", "personal").await.unwrap();
+
+    let request = MachineRequest::new(MachineConfig::builder().image("nginx").build(), None, None)
+        .with_skip_launch(true);
+    let machine = fly.machines.create("web", request).await.unwrap();
+
+    let id = machine.id.unwrap();
+    assert_eq!(fake.machine_state("web", &id).as_deref(), Some("created"));
+}
+
+#[tokio::test]
+async fn stops_starts_and_deletes() {
+    let fake = FakeFly::start().await;
+    let (fly, id, instance_id) = launch(&fake).await;
+
+    fly.machines.stop("web", &id, &instance_id).await.unwrap();
+    assert_eq!(fake.machine_state("web", &id).as_deref(), Some("stopped"));
+
+    let started = fly.machines.start("web", &id).await.unwrap();
+    assert!(!started.resumed_from_snapshot());
+    assert_eq!(fake.machine_state("web", &id).as_deref(), Some("started"));
+
+    fly.machines.delete("web", &id, true).await.unwrap();
+    assert_eq!(fake.machine_state("web", &id).as_deref(), Some("destroyed"));
+    assert!(fly.machines.list("web").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn deleting_a_running_machine_needs_force() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+
+    let err = fly.machines.delete("web", &id, false).await.unwrap_err();
+
+    assert!(err.is_conflict());
+    assert_eq!(fake.machine_state("web", &id).as_deref(), Some("started"));
+}
+
+#[tokio::test]
+async fn update_replaces_the_instance() {
+    let fake = FakeFly::start().await;
+    let (fly, id, instance_id) = launch(&fake).await;
+
+    let request = MachineRequest::new(MachineConfig::builder().image("caddy").build(), None, None);
+    let updated = fly
+        .machines
+        .update_machine("web", &id, &instance_id, request)
+        .await
+        .unwrap();
+
+    assert_ne!(updated.instance_id.unwrap(), instance_id);
+    assert_eq!(updated.state, Some(MachineLifecycleState::Started));
+    assert_eq!(updated.config.unwrap().image, "caddy");
+}
+
+#[tokio::test]
+async fn update_from_a_stale_instance_conflicts() {
+    let fake = FakeFly::start().await;
+    let (fly, id, instance_id) = launch(&fake).await;
+    let caddy = || MachineRequest::new(MachineConfig::builder().image("caddy").build(), None, None);
+
+    fly.machines
+        .update_machine("web", &id, &instance_id, caddy())
+        .await
+        .unwrap();
+    let err = fly
+        .machines
+        .update_machine("web", &id, &instance_id, caddy())
+        .await
+        .unwrap_err();
+
+    assert!(err.is_conflict());
+}
+
+#[tokio::test]
+async fn update_with_skip_launch_stages_the_config() {
+    let fake = FakeFly::start().await;
+    let (fly, id, instance_id) = launch(&fake).await;
+
+    let request = MachineRequest::new(MachineConfig::builder().image("caddy").build(), None, None)
+        .with_skip_launch(true);
+    let updated = fly
+        .machines
+        .update_machine("web", &id, &instance_id, request)
+        .await
+        .unwrap();
+
+    assert_eq!(updated.state, Some(MachineLifecycleState::Stopped));
+    assert_eq!(updated.config.unwrap().image, "caddy");
+}
+
+#[tokio::test]
+async fn wait_times_out_with_last_state() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+
+    let err = fly
+        .machines
+        .wait_for_states(
+            "web",
+            &id,
+            &[MachineState::Stopped],
+            Duration::from_millis(1500),
+            None,
+        )
+        .await
+        .unwrap_err();
+
+    assert!(err.is_timeout());
+    match err {
+        FlyError::Timeout { last_state, .. } => assert_eq!(last_state.as_deref(), Some("started")),
+        other => panic!("unexpected error: {other}"),
+    }
+}
+
+#[tokio::test]
+async fn wait_retries_throttled_and_failed_waits() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+    let waits_before = fake.request_count("/wait");
+    fake.fail_next_requests("/wait", 1, StatusCode::TOO_MANY_REQUESTS, None);
+    fake.fail_next_requests("/wait", 1, StatusCode::SERVICE_UNAVAILABLE, None);
+
+    let (machine, _) = tokio::join!(
+        fly.machines.wait_for_states(
+            "web",
+            &id,
+            &[MachineState::Stopped],
+            Duration::from_secs(5),
+            None,
+        ),
+        async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            fake.set_machine_state("web", &id, "stopped");
+        }
+    );
+
+    assert_eq!(machine.unwrap().state, Some(MachineLifecycleState::Stopped));
+    assert!(fake.request_count("/wait") >= waits_before + 2);
+}
+
+#[tokio::test]
+async fn wait_outlasts_the_client_timeout() {
+    let fake = FakeFly::start().await;
+    let (_, id, _) = launch(&fake).await;
+    let fly = FlyControl::builder()
+        .api_token(FAKE_TOKEN)
+        .base_url(&fake.base_url())
+        .timeout(Duration::from_millis(500))
+        .build()
+        .unwrap();
+    let waits_before = fake.request_count("/wait");
+
+    let (machine, _) = tokio::join!(
+        fly.machines.wait_for_states(
+            "web",
+            &id,
+            &[MachineState::Stopped],
+            Duration::from_secs(5),
+            None,
+        ),
+        async {
+            tokio::time::sleep(Duration::from_millis(1200)).await;
+            fake.set_machine_state("web", &id, "stopped");
+        }
+    );
+
+    assert_eq!(machine.unwrap().state, Some(MachineLifecycleState::Stopped));
+    assert_eq!(fake.request_count("/wait"), waits_before + 1);
+}
+
+#[tokio::test]
+async fn wait_returns_on_any_of_several_states() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+
+    let (machine, _) = tokio::join!(
+        fly.machines.wait_for_states(
+            "web",
+            &id,
+            &[MachineState::Stopped, MachineState::Failed],
+            Duration::from_secs(5),
+            None,
+        ),
+        async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            fake.set_machine_state("web", &id, "failed");
+        }
+    );
+
+    assert_eq!(machine.unwrap().state, Some(MachineLifecycleState::Failed));
+}
+
+#[tokio::test]
+async fn states_are_typed_with_an_unknown_fallback() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+
+    fake.set_machine_state("web", &id, "replacing");
+    let state = fly
+        .machines
+        .get_machine("web", &id)
+        .await
+        .unwrap()
+        .state
+        .unwrap();
+    assert_eq!(state, MachineLifecycleState::Replacing);
+    assert!(state.is_transitioning() && !state.is_running());
+
+    fake.set_machine_state("web", &id, "quarantined");
+    let state = fly
+        .machines
+        .get_machine("web", &id)
+        .await
+        .unwrap()
+        .state
+        .unwrap();
+    assert_eq!(state, MachineLifecycleState::Unknown("quarantined".into()));
+    assert_eq!(state.to_string(), "quarantined");
+    assert!(!state.is_transitioning() && !state.is_terminal());
+
+    fake.set_machine_state("web", &id, "failed");
+    let state = fly
+        .machines
+        .get_machine("web", &id)
+        .await
+        .unwrap()
+        .state
+        .unwrap();
+    assert!(state.is_terminal());
+    assert_eq!(state, MachineState::Failed);
+}
+
+#[tokio::test]
+async fn events_are_newest_first() {
+    let fake = FakeFly::start().await;
+    let (fly, id, instance_id) = launch(&fake).await;
+    fly.machines.stop("web", &id, &instance_id).await.unwrap();
+
+    let events = fly.machines.list_events("web", &id).await.unwrap();
+
+    let statuses: Vec<_> = events.iter().filter_map(|e| e.status.as_deref()).collect();
+    assert_eq!(statuses, ["stopped", "stopping", "started", "created"]);
+}
+
+#[tokio::test]
+async fn exec_uses_the_configured_handler() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+    fake.set_exec_handler(|command, _| ExecReply {
+        stdout: command.join(" "),
+        ..Default::default()
+    });
+
+    let output = fly
+        .machines
+        .execute_command("web", &id, vec!["echo", "hi"], None)
+        .await
+        .unwrap();
+
+    assert_eq!(output.exit_code, Some(0));
+    assert_eq!(output.stdout.as_deref(), Some("echo hi"));
+    assert_eq!(
+        fly.machines.list_processes("web", &id).await.unwrap().len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn unknown_machine_is_not_found() {
+    let fake = FakeFly::start().await;
+    let fly = fake.client();
+    fly.apps.create("web", "personal").await.unwrap();
+
+    let err = fly
+        .machines
+        .get_machine("web", "missing")
+        .await
+        .unwrap_err();
+
+    assert!(err.is_not_found());
+}
+
+#[tokio::test]
+async fn suspended_machines_resume_from_snapshot() {
+    let fake = FakeFly::start().await;
+    let (fly, id, instance_id) = launch(&fake).await;
+
+    fly.machines
+        .suspend("web", &id, &instance_id)
+        .await
+        .unwrap();
+    assert_eq!(fake.machine_state("web", &id).as_deref(), Some("suspended"));
+
+    let resumed = fly.machines.start("web", &id).await.unwrap();
+    assert!(resumed.resumed_from_snapshot());
+    assert_eq!(fake.machine_state("web", &id).as_deref(), Some("started"));
+
+    fly.machines.stop("web", &id, &instance_id).await.unwrap();
+    let booted = fly.machines.start("web", &id).await.unwrap();
+    assert!(!booted.resumed_from_snapshot());
+    assert_eq!(booted.previous_state, Some(MachineLifecycleState::Stopped));
+}
+
+#[tokio::test]
+async fn signals_reach_the_machine() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+
+    fly.machines
+        .signal("web", &id, Signal::SIGHUP)
+        .await
+        .unwrap();
+
+    let events = fly.machines.list_events("web", &id).await.unwrap();
+    assert_eq!(events[0].request.as_ref().unwrap()["signal"], "SIGHUP");
+    assert_eq!(fake.machine_state("web", &id).as_deref(), Some("started"));
+}
+
+#[tokio::test]
+async fn stop_with_sends_signal_and_timeout() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+
+    let options = StopOptions {
+        signal: Some(Signal::SIGINT),
+        timeout: Some(Duration::from_secs(30)),
+    };
+    fly.machines.stop_with("web", &id, options).await.unwrap();
+
+    assert_eq!(fake.machine_state("web", &id).as_deref(), Some("stopped"));
+    let events = fly.machines.list_events("web", &id).await.unwrap();
+    let stop = events
+        .iter()
+        .find(|event| event.status.as_deref() == Some("stopping"))
+        .unwrap();
+    let request = stop.request.as_ref().unwrap();
+    assert_eq!(request["signal"], "SIGINT");
+    assert_eq!(request["timeout"], "30s");
+}
+
+#[tokio::test]
+async fn stop_with_falls_back_to_the_stop_config() {
+    let fake = FakeFly::start().await;
+    let (fly, _, _) = launch(&fake).await;
+    let config = MachineConfig::builder()
+        .image("nginx")
+        .stop_config(StopConfig {
+            signal: Some("SIGTERM".to_string()),
+            timeout: Some(TimeoutConfig::new(1)),
+        })
+        .build();
+    let machine = fly
+        .machines
+        .create("web", MachineRequest::new(config, None, None))
+        .await
+        .unwrap();
+    let id = machine.id.unwrap();
+    fly.machines
+        .wait_for_machine_state("web", &id, MachineState::Started, Some(5), None)
+        .await
+        .unwrap();
+
+    fly.machines
+        .stop_with("web", &id, StopOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(fake.machine_state("web", &id).as_deref(), Some("stopped"));
+}
+
+#[tokio::test]
+async fn cordon_and_uncordon() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+
+    fly.machines.cordon("web", &id).await.unwrap();
+    assert!(fake.is_cordoned("web", &id));
+    assert_eq!(fake.machine_state("web", &id).as_deref(), Some("started"));
+
+    fly.machines.uncordon("web", &id).await.unwrap();
+    assert!(!fake.is_cordoned("web", &id));
+}
+
+fn quick_drain(timeout: Duration) -> DrainOptions {
+    DrainOptions {
+        timeout,
+        poll_interval: Duration::from_millis(20),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn drain_stops_an_idle_machine() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+
+    let settled = fly
+        .machines
+        .drain("web", &id, quick_drain(Duration::from_secs(5)))
+        .await
+        .unwrap();
+
+    assert!(settled);
+    assert!(fake.is_cordoned("web", &id));
+    assert_eq!(fake.machine_state("web", &id).as_deref(), Some("stopped"));
+}
+
+#[tokio::test]
+async fn drain_gives_up_on_a_busy_machine() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+    fake.set_busy("web", &id, true);
+
+    let settled = fly
+        .machines
+        .drain("web", &id, quick_drain(Duration::from_millis(200)))
+        .await
+        .unwrap();
+
+    assert!(!settled);
+    assert_eq!(fake.machine_state("web", &id).as_deref(), Some("stopped"));
+}
+
+#[tokio::test]
+async fn failed_drain_uncordons_the_machine() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+    fake.fail_next_requests("/ps", 1, StatusCode::NOT_FOUND, None);
+
+    let err = fly
+        .machines
+        .drain("web", &id, quick_drain(Duration::from_secs(5)))
+        .await
+        .unwrap_err();
+
+    assert!(err.is_not_found(), "{err}");
+    assert!(!fake.is_cordoned("web", &id));
+    assert_eq!(fake.machine_state("web", &id).as_deref(), Some("started"));
+}
+
+#[tokio::test]
+async fn metadata_keys_change_in_place() {
+    let fake = FakeFly::start().await;
+    let (fly, id, instance_id) = launch(&fake).await;
+
+    fly.machines
+        .set_metadata_key("web", &id, "tenant", "acme")
+        .await
+        .unwrap();
+    fly.machines
+        .set_metadata_key("web", &id, "deploy_version", "42")
+        .await
+        .unwrap();
+    fly.machines
+        .delete_metadata_key("web", &id, "deploy_version")
+        .await
+        .unwrap();
+
+    let metadata = fly.machines.get_metadata("web", &id).await.unwrap();
+    assert_eq!(metadata.len(), 1);
+    assert_eq!(metadata["tenant"], "acme");
+
+    let machine = fly.machines.get_machine("web", &id).await.unwrap();
+    assert_eq!(machine.instance_id.as_deref(), Some(instance_id.as_str()));
+    assert_eq!(machine.state, Some(MachineLifecycleState::Started));
+    assert_eq!(machine.config.unwrap().metadata.unwrap()["tenant"], "acme");
+}
+
+#[tokio::test]
+async fn metadata_keys_with_reserved_characters_stay_intact() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+
+    for key in ["team/owner", "a?b", "50%#off"] {
+        fly.machines
+            .set_metadata_key("web", &id, key, "acme")
+            .await
+            .unwrap();
+    }
+    fly.machines
+        .delete_metadata_key("web", &id, "a?b")
+        .await
+        .unwrap();
+
+    let metadata = fly.machines.get_metadata("web", &id).await.unwrap();
+    assert_eq!(metadata.len(), 2);
+    assert_eq!(metadata["team/owner"], "acme");
+    assert_eq!(metadata["50%#off"], "acme");
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use common::launch;
+use fly_sdk::machines::{
+    CpuKind, GpuKind, GuestConfig, MachineConfig, MachineLifecycleState, MachineRegions,
+    MachineRequest, MachineState, Preset,
+};
+use fly_sdk::testing::FakeFly;
+
+#[tokio::test]
+async fn resize_to_a_preset_keeps_the_rest_of_the_config() {
+    let fake = FakeFly::start().await;
+    let fly = fake.client();
+    fly.apps.create("web", "personal").await.unwrap();
+    let config = MachineConfig::builder()
+        .image("nginx")
+        .add_env("PORT", "8080")
+        .build();
+    let machine = fly
+        .machines
+        .create(
+            "web",
+            MachineRequest::new(config, Some("api".to_string()), Some(MachineRegions::Fra)),
+        )
+        .await
+        .unwrap();
+    let id = machine.id.unwrap();
+    fly.machines
+        .wait_for_machine_state("web", &id, MachineState::Started, Some(5), None)
+        .await
+        .unwrap();
+
+    let resized = fly
+        .machines
+        .resize("web", &id, Preset::Performance2x)
+        .await
+        .unwrap();
+
+    assert_ne!(resized.instance_id, machine.instance_id);
+    assert_eq!(resized.state, Some(MachineLifecycleState::Started));
+    assert_eq!(resized.name.as_deref(), Some("api"));
+    assert_eq!(resized.region.as_deref(), Some("fra"));
+    let config = resized.config.unwrap();
+    assert_eq!(config.env.unwrap()["PORT"], "8080");
+    let guest = config.guest.unwrap();
+    assert!(matches!(guest.cpu_kind, Some(CpuKind::Performance)));
+    assert_eq!((guest.cpus, guest.memory_mb), (Some(2), Some(4096)));
+}
+
+#[tokio::test]
+async fn presets_replace_a_gpu_guest() {
+    let fake = FakeFly::start().await;
+    let fly = fake.client();
+    fly.apps.create("web", "personal").await.unwrap();
+    let config = MachineConfig::builder()
+        .image("ollama")
+        .cpu_kind(CpuKind::Performance)
+        .cpus(8)
+        .memory(32768)
+        .gpu_kind(GpuKind::A10)
+        .gpus(1)
+        .build();
+    let machine = fly
+        .machines
+        .create("web", MachineRequest::new(config, None, None))
+        .await
+        .unwrap();
+    let id = machine.id.unwrap();
+
+    let resized = fly
+        .machines
+        .resize("web", &id, Preset::SharedCpu2x)
+        .await
+        .unwrap();
+
+    let guest = resized.config.unwrap().guest.unwrap();
+    assert!(matches!(guest.cpu_kind, Some(CpuKind::Shared)));
+    assert_eq!((guest.cpus, guest.memory_mb), (Some(2), Some(512)));
+    assert!(guest.gpu_kind.is_none());
+    assert_eq!(guest.gpus, None);
+}
+
+#[tokio::test]
+async fn invalid_sizes_are_rejected_before_any_change() {
+    let fake = FakeFly::start().await;
+    let (fly, id, instance_id) = launch(&fake).await;
+
+    for (cpu_kind, cpus, memory_mb) in [
+        (CpuKind::Shared, 3, 768),
+        (CpuKind::Shared, 1, 4096),
+        (CpuKind::Performance, 2, 2048),
+        (CpuKind::Performance, 1, 2100),
+    ] {
+        let size = GuestConfig {
+            cpu_kind: Some(cpu_kind),
+            cpus: Some(cpus),
+            memory_mb: Some(memory_mb),
+            ..GuestConfig::default()
+        };
+        let err = fly.machines.resize("web", &id, size).await.unwrap_err();
+        assert!(matches!(err, fly_sdk::FlyError::Config(_)), "{err}");
+    }
+
+    let machine = fly.machines.get_machine("web", &id).await.unwrap();
+    assert_eq!(machine.instance_id.as_deref(), Some(instance_id.as_str()));
+}
+
+#[tokio::test]
+async fn resizing_a_stopped_machine_leaves_it_stopped() {
+    let fake = FakeFly::start().await;
+    let (fly, id, instance_id) = launch(&fake).await;
+    fly.machines.stop("web", &id, &instance_id).await.unwrap();
+
+    let resized = fly
+        .machines
+        .resize("web", &id, Preset::SharedCpu2x)
+        .await
+        .unwrap();
+
+    assert_eq!(resized.state, Some(MachineLifecycleState::Stopped));
+    let guest = resized.config.unwrap().guest.unwrap();
+    assert_eq!((guest.cpus, guest.memory_mb), (Some(2), Some(512)));
+}
+
+#[tokio::test]
+async fn unset_size_fields_keep_their_current_values() {
+    let fake = FakeFly::start().await;
+    let (fly, id, _) = launch(&fake).await;
+    fly.machines
+        .resize("web", &id, Preset::Performance2x)
+        .await
+        .unwrap();
+
+    let more_memory = GuestConfig {
+        cpu_kind: None,
+        cpus: None,
+        memory_mb: Some(8192),
+        ..GuestConfig::default()
+    };
+    let resized = fly.machines.resize("web", &id, more_memory).await.unwrap();
+
+    let guest = resized.config.unwrap().guest.unwrap();
+    assert!(matches!(guest.cpu_kind, Some(CpuKind::Performance)));
+    assert_eq!((guest.cpus, guest.memory_mb), (Some(2), Some(8192)));
+}
@@ -0,0 +1,31 @@
+use fly_sdk::secrets::SecretValue;
+use fly_sdk::testing::FakeFly;
+
+#[tokio::test]
+async fn manages_secrets() {
+    let fake = FakeFly::start().await;
+    let fly = fake.client();
+    fly.apps.create("web", "personal").await.unwrap();
+
+    let secret = fly
+        .secrets
+        .create_secret(
+            "web",
+            "db-key",
+            "secret_key",
+            SecretValue::new(vec![1, 2, 3]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(secret.label, "db-key");
+    fly.secrets
+        .generate_secret("web", "signing-key", "signing_key")
+        .await
+        .unwrap();
+    assert_eq!(fly.secrets.list_secrets("web").await.unwrap().len(), 2);
+
+    fly.secrets.destroy_secret("web", "db-key").await.unwrap();
+    let secrets = fly.secrets.list_secrets("web").await.unwrap();
+    assert_eq!(secrets.len(), 1);
+    assert_eq!(secrets[0].stype, "signing_key");
+}
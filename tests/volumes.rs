@@ -0,0 +1,66 @@
+use fly_sdk::machines::MachineRegions;
+use fly_sdk::testing::FakeFly;
+use fly_sdk::volumes::{CreateVolumeRequest, ExtendVolumeRequest, UpdateVolumeRequest};
+
+#[tokio::test]
+async fn manages_volumes_and_snapshots() {
+    let fake = FakeFly::start().await;
+    let fly = fake.client();
+    fly.apps.create("db", "personal").await.unwrap();
+
+    let request = CreateVolumeRequest::builder("data", MachineRegions::Ams, 1).build();
+    let volume = fly.volumes.create_volume("db", request).await.unwrap();
+    let id = volume.id.unwrap();
+    assert_eq!(volume.region.as_deref(), Some("ams"));
+
+    let update = UpdateVolumeRequest {
+        auto_backup_enabled: false,
+        snapshot_retention: 10,
+    };
+    let updated = fly.volumes.update_volume("db", &id, update).await.unwrap();
+    assert_eq!(updated.snapshot_retention, Some(10));
+
+    let extended = fly
+        .volumes
+        .extend_volume("db", &id, ExtendVolumeRequest { size_gb: 3 })
+        .await
+        .unwrap();
+    assert_eq!(extended.size_gb, Some(3));
+
+    fly.volumes.create_snapshot("db", &id).await.unwrap();
+    assert_eq!(
+        fly.volumes.list_snapshots("db", &id).await.unwrap().len(),
+        1
+    );
+
+    fly.volumes.destroy_volume("db", &id).await.unwrap();
+    assert!(fly
+        .volumes
+        .list_volumes("db", false)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn volumes_cannot_shrink() {
+    let fake = FakeFly::start().await;
+    let fly = fake.client();
+    fly.apps.create("db", "personal").await.unwrap();
+    let request = CreateVolumeRequest::builder("data", MachineRegions::Ams, 2).build();
+    let id = fly
+        .volumes
+        .create_volume("db", request)
+        .await
+        .unwrap()
+        .id
+        .unwrap();
+
+    let err = fly
+        .volumes
+        .extend_volume("db", &id, ExtendVolumeRequest { size_gb: 1 })
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.status().map(|status| status.as_u16()), Some(400));
+}